@@ -1,11 +1,6 @@
 -- Basic schema
-
-CREATE TABLE IF NOT EXISTS iss_fetch_log (
-    id BIGSERIAL PRIMARY KEY,
-    fetched_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    source_url TEXT NOT NULL,
-    payload JSONB NOT NULL
-);
+-- Таблицы rust_iss (iss_fetch_log, osdr_items, space_cache, ...) создаются его миграциями:
+-- services/rust-iss/migrations
 
 CREATE TABLE IF NOT EXISTS telemetry_legacy (
     id BIGSERIAL PRIMARY KEY,
@@ -12,7 +12,9 @@ class DashboardController extends Controller
     private function getJson(string $url, array $qs = []): array {
         if ($qs) $url .= (str_contains($url,'?')?'&':'?') . http_build_query($qs);
         $raw = @file_get_contents($url);
-        return $raw ? (json_decode($raw, true) ?: []) : [];
+        $js  = $raw ? (json_decode($raw, true) ?: []) : [];
+        // rust_iss отвечает конвертом {ok, data}
+        return ($js['ok'] ?? false) === true ? ($js['data'] ?? []) : [];
     }
 
     public function index()
@@ -11,8 +11,8 @@ class IssController extends Controller
         $last  = @file_get_contents($base.'/last');
         $trend = @file_get_contents($base.'/iss/trend');
 
-        $lastJson  = $last  ? json_decode($last,  true) : [];
-        $trendJson = $trend ? json_decode($trend, true) : [];
+        $lastJson  = $last  ? (json_decode($last,  true)['data'] ?? []) : [];
+        $trendJson = $trend ? (json_decode($trend, true)['data'] ?? []) : [];
 
         return view('iss', ['last' => $lastJson, 'trend' => $trendJson, 'base' => $base]);
     }
@@ -17,7 +17,7 @@ class OsdrController extends Controller
         $base  = getenv('RUST_BASE') ?: 'http://rust_iss:3000';
 
         $json  = @file_get_contents($base.'/osdr/list?limit='.$limit);
-        $data  = $json ? json_decode($json, true) : ['items' => []];
+        $data  = $json ? (json_decode($json, true)['data'] ?? []) : ['items' => []];
         $items = $data['items'] ?? [];
 
         $items = $this->flattenOsdr($items);
@@ -17,6 +17,11 @@ class ProxyController extends Controller
         return $this->pipe('/iss/trend' . ($q ? '?' . $q : ''));
     }
 
+    public function track() {
+        $q = request()->getQueryString();
+        return $this->pipe('/iss/track' . ($q ? '?' . $q : ''));
+    }
+
     private function pipe(string $path)
     {
         $url = $this->base() . $path;
@@ -28,9 +33,12 @@ class ProxyController extends Controller
             if ($body === false || trim($body) === '') {
                 $body = '{}';
             }
-            json_decode($body);
+            $js = json_decode($body, true);
             if (json_last_error() !== JSON_ERROR_NONE) {
                 $body = '{}';
+            } elseif (is_array($js) && ($js['ok'] ?? null) === true) {
+                // конверт rust_iss {ok, data} — фронту отдаём только data
+                $body = json_encode($js['data'] ?? new \stdClass());
             }
             return new Response($body, 200, ['Content-Type' => 'application/json']);
         } catch (\Throwable $e) {
@@ -119,15 +119,25 @@ document.addEventListener('DOMContentLoaded', async function () {
       options: { responsive: true, scales: { x: { display: false } } }
     });
 
-    async function loadTrend() {
+    // наземный трек за последний виток, уже разрезанный по антимеридиану
+    async function loadTrack() {
       try {
-        const r = await fetch('/api/iss/trend?limit=240');
+        const r = await fetch('/api/iss/track?max_points=300');
         const js = await r.json();
-        const pts = Array.isArray(js.points) ? js.points.map(p => [p.lat, p.lon]) : [];
-        if (pts.length) {
-          trail.setLatLngs(pts);
-          marker.setLatLng(pts[pts.length-1]);
+        const segs = (js.segments||[]).map(s => s.map(p => [p.lat, p.lon])).filter(s => s.length);
+        if (segs.length) {
+          trail.setLatLngs(segs);
+          const last = segs[segs.length-1];
+          marker.setLatLng(last[last.length-1]);
         }
+      } catch(e) {}
+    }
+
+    async function loadTrend() {
+      try {
+        const r = await fetch('/api/iss/trend?window=8h&limit=240');
+        const js = await r.json();
+        loadTrack();
         const t = (js.points||[]).map(p => new Date(p.at).toLocaleTimeString());
         speedChart.data.labels = t;
         speedChart.data.datasets[0].data = (js.points||[]).map(p => p.velocity);
@@ -139,6 +149,17 @@ document.addEventListener('DOMContentLoaded', async function () {
     }
     loadTrend();
     setInterval(loadTrend, 15000);
+
+    // маркер двигается сразу по новой позиции; при обрыве EventSource переподключается сам
+    if (typeof EventSource !== 'undefined') {
+      const live = new EventSource('/api/iss/stream');
+      live.addEventListener('position', ev => {
+        try {
+          const p = JSON.parse(ev.data);
+          marker.setLatLng([p.lat, p.lon]);
+        } catch(e) {}
+      });
+    }
   }
 
   // ====== JWST ГАЛЕРЕЯ ======
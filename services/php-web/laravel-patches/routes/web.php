@@ -13,6 +13,7 @@ Route::get('/legacy',    [\App\Http\Controllers\LegacyController::class,    'ind
 // Прокси к rust_iss
 Route::get('/api/iss/last',  [\App\Http\Controllers\ProxyController::class, 'last']);
 Route::get('/api/iss/trend', [\App\Http\Controllers\ProxyController::class, 'trend']);
+Route::get('/api/iss/track', [\App\Http\Controllers\ProxyController::class, 'track']);
 
 // JWST галерея (JSON)
 Route::get('/api/jwst/feed', [\App\Http\Controllers\DashboardController::class, 'jwstFeed']);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS space_cache;
+DROP TABLE IF EXISTS osdr_items;
+DROP TABLE IF EXISTS iss_fetch_log;
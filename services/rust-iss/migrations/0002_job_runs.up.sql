@@ -0,0 +1,12 @@
+-- Состояние фоновых задач (GET /jobs, пауза переживает рестарт)
+
+CREATE TABLE IF NOT EXISTS job_runs(
+    name TEXT PRIMARY KEY,
+    interval_seconds BIGINT NOT NULL,
+    paused BOOLEAN NOT NULL DEFAULT false,
+    run_count BIGINT NOT NULL DEFAULT 0,
+    last_started TIMESTAMPTZ,
+    last_finished TIMESTAMPTZ,
+    last_error TEXT,
+    next_run_at TIMESTAMPTZ
+);
@@ -0,0 +1,12 @@
+DROP INDEX IF EXISTS ix_iss_fetch_log_position_at;
+
+ALTER TABLE iss_fetch_log
+    DROP COLUMN IF EXISTS lat,
+    DROP COLUMN IF EXISTS lon,
+    DROP COLUMN IF EXISTS altitude_km,
+    DROP COLUMN IF EXISTS velocity_kmh,
+    DROP COLUMN IF EXISTS visibility,
+    DROP COLUMN IF EXISTS footprint_km,
+    DROP COLUMN IF EXISTS solar_lat,
+    DROP COLUMN IF EXISTS solar_lon,
+    DROP COLUMN IF EXISTS position_at;
@@ -0,0 +1,39 @@
+-- Типизированная позиция МКС рядом с сырым payload wheretheiss.at.
+-- Исторические строки заполняются из payload; нераспознанные остаются с NULL.
+
+ALTER TABLE iss_fetch_log
+    ADD COLUMN IF NOT EXISTS lat DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS lon DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS altitude_km DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS velocity_kmh DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS visibility TEXT,
+    ADD COLUMN IF NOT EXISTS footprint_km DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS solar_lat DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS solar_lon DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS position_at TIMESTAMPTZ;
+
+-- число в payload бывает и JSON-числом, и строкой
+CREATE FUNCTION pg_temp.jnum(v JSONB) RETURNS DOUBLE PRECISION AS $$
+    SELECT CASE jsonb_typeof(v)
+        WHEN 'number' THEN (v #>> '{}')::double precision
+        WHEN 'string' THEN CASE WHEN (v #>> '{}') ~ '^\s*-?[0-9]+(\.[0-9]+)?([eE][-+]?[0-9]+)?\s*$'
+                                THEN (v #>> '{}')::double precision END
+    END
+$$ LANGUAGE sql IMMUTABLE;
+
+UPDATE iss_fetch_log SET
+    lat          = pg_temp.jnum(payload -> 'latitude'),
+    lon          = pg_temp.jnum(payload -> 'longitude'),
+    altitude_km  = pg_temp.jnum(payload -> 'altitude'),
+    velocity_kmh = pg_temp.jnum(payload -> 'velocity'),
+    visibility   = payload ->> 'visibility',
+    footprint_km = pg_temp.jnum(payload -> 'footprint'),
+    solar_lat    = pg_temp.jnum(payload -> 'solar_lat'),
+    solar_lon    = pg_temp.jnum(payload -> 'solar_lon'),
+    position_at  = COALESCE(to_timestamp(pg_temp.jnum(payload -> 'timestamp')), fetched_at)
+WHERE lat IS NULL;
+
+-- строка без координат — не позиция
+UPDATE iss_fetch_log SET position_at = NULL WHERE lat IS NULL OR lon IS NULL;
+
+CREATE INDEX IF NOT EXISTS ix_iss_fetch_log_position_at ON iss_fetch_log(position_at) WHERE lat IS NOT NULL;
@@ -0,0 +1,3 @@
+DELETE FROM iss_fetch_log WHERE source = 'predicted';
+ALTER TABLE iss_fetch_log DROP COLUMN IF EXISTS source;
+DROP TABLE IF EXISTS tle_sets;
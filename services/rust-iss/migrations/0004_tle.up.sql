@@ -0,0 +1,17 @@
+-- Орбитальные элементы для SGP4 и пометка рассчитанных позиций
+
+CREATE TABLE IF NOT EXISTS tle_sets(
+    id BIGSERIAL PRIMARY KEY,
+    norad_id INT NOT NULL,
+    epoch TIMESTAMPTZ NOT NULL,
+    name TEXT,
+    line1 TEXT NOT NULL,
+    line2 TEXT NOT NULL,
+    -- URL или путь к файлу, откуда набор пришёл
+    origin TEXT NOT NULL,
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (norad_id, epoch)
+);
+
+-- observed — ответ wheretheiss.at, predicted — SGP4 по TLE, пока апстрим недоступен
+ALTER TABLE iss_fetch_log ADD COLUMN IF NOT EXISTS source TEXT NOT NULL DEFAULT 'observed';
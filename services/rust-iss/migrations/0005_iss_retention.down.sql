@@ -0,0 +1,35 @@
+-- Обратно в обычную таблицу. Строки, уже удалённые по сроку хранения, не вернуть — остаются только в агрегатах,
+-- которые здесь тоже удаляются.
+
+CREATE TABLE iss_fetch_log_unpartitioned(
+    id BIGINT NOT NULL DEFAULT nextval('iss_fetch_log_id_seq') PRIMARY KEY,
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    source_url TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    lat DOUBLE PRECISION,
+    lon DOUBLE PRECISION,
+    altitude_km DOUBLE PRECISION,
+    velocity_kmh DOUBLE PRECISION,
+    visibility TEXT,
+    footprint_km DOUBLE PRECISION,
+    solar_lat DOUBLE PRECISION,
+    solar_lon DOUBLE PRECISION,
+    position_at TIMESTAMPTZ,
+    source TEXT NOT NULL DEFAULT 'observed'
+);
+
+INSERT INTO iss_fetch_log_unpartitioned
+SELECT id, fetched_at, source_url, payload, lat, lon, altitude_km, velocity_kmh,
+       visibility, footprint_km, solar_lat, solar_lon, position_at, source
+FROM iss_fetch_log;
+
+ALTER SEQUENCE iss_fetch_log_id_seq OWNED BY iss_fetch_log_unpartitioned.id;
+DROP TABLE iss_fetch_log;
+DROP FUNCTION IF EXISTS iss_fetch_log_ensure_partition(DATE);
+
+ALTER TABLE iss_fetch_log_unpartitioned RENAME TO iss_fetch_log;
+ALTER TABLE iss_fetch_log RENAME CONSTRAINT iss_fetch_log_unpartitioned_pkey TO iss_fetch_log_pkey;
+CREATE INDEX IF NOT EXISTS ix_iss_fetch_log_position_at ON iss_fetch_log(position_at) WHERE lat IS NOT NULL;
+
+DROP TABLE IF EXISTS iss_rollup_daily;
+DROP TABLE IF EXISTS iss_rollup_hourly;
@@ -0,0 +1,88 @@
+-- iss_fetch_log секционируется по месяцам fetched_at (UTC): старые месяцы удаляются целиком.
+-- Перед удалением сырые строки сворачиваются в почасовые и суточные агрегаты (задача iss_rollup).
+
+ALTER TABLE iss_fetch_log RENAME TO iss_fetch_log_unpartitioned;
+ALTER TABLE iss_fetch_log_unpartitioned RENAME CONSTRAINT iss_fetch_log_pkey TO iss_fetch_log_unpartitioned_pkey;
+DROP INDEX IF EXISTS ix_iss_fetch_log_position_at;
+
+-- ключ секционирования обязан входить в первичный ключ
+CREATE TABLE iss_fetch_log(
+    id BIGINT NOT NULL DEFAULT nextval('iss_fetch_log_id_seq'),
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    source_url TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    lat DOUBLE PRECISION,
+    lon DOUBLE PRECISION,
+    altitude_km DOUBLE PRECISION,
+    velocity_kmh DOUBLE PRECISION,
+    visibility TEXT,
+    footprint_km DOUBLE PRECISION,
+    solar_lat DOUBLE PRECISION,
+    solar_lon DOUBLE PRECISION,
+    position_at TIMESTAMPTZ,
+    source TEXT NOT NULL DEFAULT 'observed',
+    PRIMARY KEY (id, fetched_at)
+) PARTITION BY RANGE (fetched_at);
+-- иначе DROP старой таблицы унесёт и последовательность
+ALTER SEQUENCE iss_fetch_log_id_seq OWNED BY iss_fetch_log.id;
+
+-- страховка на случай, если задача долго не создавала секции впрок
+CREATE TABLE iss_fetch_log_default PARTITION OF iss_fetch_log DEFAULT;
+
+-- Секция на месяц `month` (UTC); строки этого месяца из default-секции переносятся в неё.
+-- Возвращает имя созданной секции или NULL, если она уже есть.
+CREATE OR REPLACE FUNCTION iss_fetch_log_ensure_partition(month DATE) RETURNS TEXT AS $$
+DECLARE
+    m DATE := date_trunc('month', month)::date;
+    part TEXT := format('iss_fetch_log_p%s', to_char(m, 'YYYYMM'));
+    lo TIMESTAMPTZ := m::timestamp AT TIME ZONE 'UTC';
+    hi TIMESTAMPTZ := (m + interval '1 month')::timestamp AT TIME ZONE 'UTC';
+BEGIN
+    IF to_regclass(part) IS NOT NULL THEN
+        RETURN NULL;
+    END IF;
+    EXECUTE format('CREATE TABLE %I (LIKE iss_fetch_log INCLUDING DEFAULTS)', part);
+    EXECUTE format('WITH moved AS (DELETE FROM iss_fetch_log_default WHERE fetched_at >= %L AND fetched_at < %L RETURNING *)
+                    INSERT INTO %I SELECT * FROM moved', lo, hi, part);
+    EXECUTE format('ALTER TABLE iss_fetch_log ATTACH PARTITION %I FOR VALUES FROM (%L) TO (%L)', part, lo, hi);
+    RETURN part;
+END
+$$ LANGUAGE plpgsql;
+
+SELECT iss_fetch_log_ensure_partition(m::date)
+FROM generate_series(
+    date_trunc('month', COALESCE((SELECT min(fetched_at) FROM iss_fetch_log_unpartitioned), now()) AT TIME ZONE 'UTC'),
+    date_trunc('month', now() AT TIME ZONE 'UTC') + interval '1 month',
+    interval '1 month'
+) AS m;
+
+INSERT INTO iss_fetch_log (id, fetched_at, source_url, payload, lat, lon, altitude_km, velocity_kmh,
+                           visibility, footprint_km, solar_lat, solar_lon, position_at, source)
+SELECT id, fetched_at, source_url, payload, lat, lon, altitude_km, velocity_kmh,
+       visibility, footprint_km, solar_lat, solar_lon, position_at, source
+FROM iss_fetch_log_unpartitioned;
+
+DROP TABLE iss_fetch_log_unpartitioned;
+
+CREATE INDEX ix_iss_fetch_log_fetched_at ON iss_fetch_log(fetched_at);
+CREATE INDEX ix_iss_fetch_log_position_at ON iss_fetch_log(position_at) WHERE lat IS NOT NULL;
+
+-- Агрегаты по времени измерения (position_at). Статистика — только по observed-позициям,
+-- first_* — первая позиция корзины любого источника (точка для трека).
+CREATE TABLE iss_rollup_hourly(
+    bucket TIMESTAMPTZ PRIMARY KEY,
+    samples INT NOT NULL,
+    predicted_samples INT NOT NULL,
+    altitude_avg DOUBLE PRECISION,
+    altitude_min DOUBLE PRECISION,
+    altitude_max DOUBLE PRECISION,
+    velocity_avg DOUBLE PRECISION,
+    velocity_min DOUBLE PRECISION,
+    velocity_max DOUBLE PRECISION,
+    first_at TIMESTAMPTZ NOT NULL,
+    first_lat DOUBLE PRECISION NOT NULL,
+    first_lon DOUBLE PRECISION NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE iss_rollup_daily (LIKE iss_rollup_hourly INCLUDING ALL);
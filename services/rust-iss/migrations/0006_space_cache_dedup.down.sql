@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS space_cache_stats;
+ALTER TABLE space_cache DROP COLUMN IF EXISTS last_seen_at;
+ALTER TABLE space_cache DROP COLUMN IF EXISTS content_hash;
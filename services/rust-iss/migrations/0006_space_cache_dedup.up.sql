@@ -0,0 +1,23 @@
+-- Дедупликация снимков space_cache по хешу содержимого и счётчики очистки
+
+-- Хеш канонического текста jsonb (ключи отсортированы), тем же выражением считает запись
+ALTER TABLE space_cache ADD COLUMN IF NOT EXISTS content_hash TEXT;
+-- Когда этот снимок последний раз пришёл от апстрима (fetched_at — когда впервые)
+ALTER TABLE space_cache ADD COLUMN IF NOT EXISTS last_seen_at TIMESTAMPTZ;
+UPDATE space_cache
+SET content_hash = encode(sha256(convert_to(payload::text, 'UTF8')), 'hex'),
+    last_seen_at = fetched_at;
+ALTER TABLE space_cache
+    ALTER COLUMN content_hash SET NOT NULL,
+    ALTER COLUMN last_seen_at SET NOT NULL,
+    ALTER COLUMN last_seen_at SET DEFAULT now();
+
+-- Накопленные метрики по источнику: сколько раз пришёл неизменный снимок и сколько удалила очистка
+CREATE TABLE IF NOT EXISTS space_cache_stats(
+    source TEXT PRIMARY KEY,
+    dedup_hits BIGINT NOT NULL DEFAULT 0,
+    rows_pruned BIGINT NOT NULL DEFAULT 0,
+    -- pg_column_size(payload) удалённых строк: то, что занимал снимок, без заголовков строк
+    bytes_reclaimed BIGINT NOT NULL DEFAULT 0,
+    last_pruned_at TIMESTAMPTZ
+);
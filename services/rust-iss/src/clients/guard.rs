@@ -0,0 +1,291 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use crate::domain::UpstreamHealth;
+
+/// Ошибки, которые клиент возвращает без похода в сеть
+#[derive(Debug, thiserror::Error)]
+pub enum GuardError {
+    #[error("circuit open for {host}, retry in {}s", retry_in.as_secs())]
+    CircuitOpen { host: String, retry_in: Duration },
+    #[error("rate limit for {host} exhausted, retry in {}s", retry_in.as_secs())]
+    RateLimited { host: String, retry_in: Duration },
+}
+
+#[derive(Clone, Debug)]
+pub struct GuardConfig {
+    /// Размер корзины (сколько запросов можно сделать пачкой)
+    pub burst: u32,
+    /// Скорость пополнения корзины
+    pub per_hour: u32,
+    /// Сколько подряд неудач открывают breaker
+    pub failure_threshold: u32,
+    /// Через сколько открытый breaker пропускает пробный запрос
+    pub cooldown: Duration,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        Self { burst: 20, per_hour: 3600, failure_threshold: 5, cooldown: Duration::from_secs(60) }
+    }
+}
+
+#[derive(Debug)]
+enum Breaker {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { probing: bool },
+}
+
+#[derive(Debug)]
+struct State {
+    tokens: f64,
+    refilled_at: Instant,
+    breaker: Breaker,
+    last_failures: u32,
+}
+
+/// Пропуск breaker'а на один логический вызов
+#[must_use]
+pub struct Admission<'a> {
+    guard: &'a HostGuard,
+    /// Это пробный запрос half-open
+    probe: bool,
+    settled: bool,
+}
+
+impl Admission<'_> {
+    /// Апстрим ответил (в том числе отказом 4xx)
+    pub fn success(mut self) {
+        self.settled = true;
+        self.guard.record_success();
+    }
+
+    /// Апстрим недоступен или отвечает 5xx/429
+    pub fn failure(mut self) {
+        self.settled = true;
+        self.guard.record_failure();
+    }
+}
+
+impl Drop for Admission<'_> {
+    fn drop(&mut self) {
+        if self.probe && !self.settled {
+            self.guard.release_probe();
+        }
+    }
+}
+
+/// Token bucket + circuit breaker на один хост апстрима
+#[derive(Debug)]
+pub struct HostGuard {
+    host: String,
+    cfg: GuardConfig,
+    state: Mutex<State>,
+}
+
+impl HostGuard {
+    pub fn new(host: impl Into<String>, cfg: GuardConfig) -> Self {
+        let state = State {
+            tokens: cfg.burst as f64,
+            refilled_at: Instant::now(),
+            breaker: Breaker::Closed { failures: 0 },
+            last_failures: 0,
+        };
+        Self { host: host.into(), cfg, state: Mutex::new(state) }
+    }
+
+    fn refill(&self, st: &mut State) {
+        let now = Instant::now();
+        let per_sec = self.cfg.per_hour as f64 / 3600.0;
+        st.tokens = (st.tokens + now.duration_since(st.refilled_at).as_secs_f64() * per_sec).min(self.cfg.burst as f64);
+        st.refilled_at = now;
+    }
+
+    /// Пропускает ли breaker логический вызов (до всех повторов). Итог вызова сообщается через
+    /// [`Admission`]; если она брошена без итога (future отменили), слот пробы освобождается сам.
+    pub fn admit(&self) -> Result<Admission<'_>, GuardError> {
+        let mut st = self.state.lock().unwrap();
+        let probe = match st.breaker {
+            Breaker::Closed { .. } => false,
+            Breaker::Open { until } => {
+                let now = Instant::now();
+                if now < until {
+                    return Err(GuardError::CircuitOpen { host: self.host.clone(), retry_in: until - now });
+                }
+                true
+            }
+            // в half-open пропускаем ровно один пробный запрос
+            Breaker::HalfOpen { probing: true } => return Err(GuardError::CircuitOpen { host: self.host.clone(), retry_in: Duration::ZERO }),
+            Breaker::HalfOpen { probing: false } => true,
+        };
+        if probe {
+            st.breaker = Breaker::HalfOpen { probing: true };
+        }
+        Ok(Admission { guard: self, probe, settled: false })
+    }
+
+    /// Забрать токен на одну попытку HTTP
+    pub fn take_token(&self) -> Result<(), GuardError> {
+        let mut st = self.state.lock().unwrap();
+        self.refill(&mut st);
+        if st.tokens >= 1.0 {
+            st.tokens -= 1.0;
+            return Ok(());
+        }
+        let per_sec = self.cfg.per_hour as f64 / 3600.0;
+        let retry_in = Duration::from_secs_f64((1.0 - st.tokens) / per_sec);
+        Err(GuardError::RateLimited { host: self.host.clone(), retry_in })
+    }
+
+    fn record_success(&self) {
+        let mut st = self.state.lock().unwrap();
+        st.breaker = Breaker::Closed { failures: 0 };
+        st.last_failures = 0;
+    }
+
+    fn record_failure(&self) {
+        let mut st = self.state.lock().unwrap();
+        let failures = match st.breaker {
+            Breaker::Closed { failures } => failures + 1,
+            _ => self.cfg.failure_threshold,
+        };
+        st.last_failures = failures;
+        st.breaker = if failures >= self.cfg.failure_threshold {
+            Breaker::Open { until: Instant::now() + self.cfg.cooldown }
+        } else {
+            Breaker::Closed { failures }
+        };
+    }
+
+    /// Пробный запрос в half-open не дошёл до апстрима (упёрлись в лимит, вызов отменён) — отпускаем слот
+    fn release_probe(&self) {
+        let mut st = self.state.lock().unwrap();
+        if let Breaker::HalfOpen { probing: true } = st.breaker {
+            st.breaker = Breaker::HalfOpen { probing: false };
+        }
+    }
+
+    pub fn health(&self) -> UpstreamHealth {
+        let mut st = self.state.lock().unwrap();
+        self.refill(&mut st);
+        let (state, open_until) = match st.breaker {
+            Breaker::Closed { .. } => ("closed", None),
+            Breaker::Open { until } => {
+                let left = until.saturating_duration_since(Instant::now());
+                ("open", chrono::Duration::from_std(left).ok().map(|d| Utc::now() + d))
+            }
+            Breaker::HalfOpen { .. } => ("half_open", None),
+        };
+        UpstreamHealth {
+            host: self.host.clone(),
+            circuit: state,
+            consecutive_failures: st.last_failures,
+            open_until,
+            tokens_available: st.tokens.floor() as u32,
+            rate_per_hour: self.cfg.per_hour,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_millis(30);
+
+    fn guard(burst: u32, per_hour: u32) -> HostGuard {
+        HostGuard::new("test", GuardConfig { burst, per_hour, failure_threshold: 2, cooldown: COOLDOWN })
+    }
+
+    fn circuit(g: &HostGuard) -> &'static str {
+        g.health().circuit
+    }
+
+    #[test]
+    fn opens_after_threshold_and_rejects_until_cooldown() {
+        let g = guard(10, 3600);
+        g.admit().unwrap().failure();
+        assert_eq!(circuit(&g), "closed");
+        // успех сбрасывает счётчик подряд идущих неудач
+        g.admit().unwrap().success();
+        g.admit().unwrap().failure();
+        assert_eq!(circuit(&g), "closed");
+        g.admit().unwrap().failure();
+        assert_eq!(circuit(&g), "open");
+        assert!(matches!(g.admit(), Err(GuardError::CircuitOpen { .. })));
+    }
+
+    #[test]
+    fn half_open_probe_closes_or_reopens() {
+        let g = guard(10, 3600);
+        g.admit().unwrap().failure();
+        g.admit().unwrap().failure();
+        std::thread::sleep(COOLDOWN);
+
+        let probe = g.admit().unwrap();
+        assert_eq!(circuit(&g), "half_open");
+        // второй вызов ждёт результата пробы
+        assert!(matches!(g.admit(), Err(GuardError::CircuitOpen { .. })));
+        probe.failure();
+        assert_eq!(circuit(&g), "open");
+        assert!(g.admit().is_err());
+
+        std::thread::sleep(COOLDOWN);
+        g.admit().unwrap().success();
+        assert_eq!(circuit(&g), "closed");
+        assert_eq!(g.health().consecutive_failures, 0);
+    }
+
+    #[test]
+    fn dropped_probe_releases_slot() {
+        let g = guard(10, 3600);
+        g.admit().unwrap().failure();
+        g.admit().unwrap().failure();
+        std::thread::sleep(COOLDOWN);
+
+        drop(g.admit().unwrap());
+        assert_eq!(circuit(&g), "half_open");
+        let probe = g.admit().expect("slot must be free after an abandoned probe");
+        probe.success();
+        assert_eq!(circuit(&g), "closed");
+    }
+
+    #[test]
+    fn dropped_regular_call_keeps_probe_slot() {
+        let g = guard(10, 3600);
+        let regular = g.admit().unwrap();
+        g.admit().unwrap().failure();
+        g.admit().unwrap().failure();
+        std::thread::sleep(COOLDOWN);
+        let _probe = g.admit().unwrap();
+        // брошенный обычный вызов не отпускает чужую пробу
+        drop(regular);
+        assert!(g.admit().is_err());
+    }
+
+    #[test]
+    fn token_bucket_limits_burst_and_refills() {
+        // 36000/ч = 10 токенов в секунду
+        let g = guard(2, 36_000);
+        g.take_token().unwrap();
+        g.take_token().unwrap();
+        match g.take_token() {
+            Err(GuardError::RateLimited { retry_in, .. }) => assert!(retry_in <= Duration::from_millis(100)),
+            other => panic!("expected RateLimited, got {other:?}"),
+        }
+        assert_eq!(g.health().tokens_available, 0);
+        std::thread::sleep(Duration::from_millis(120));
+        g.take_token().unwrap();
+    }
+
+    #[test]
+    fn slow_refill_reports_wait() {
+        let g = guard(1, 60);
+        g.take_token().unwrap();
+        match g.take_token() {
+            Err(GuardError::RateLimited { retry_in, .. }) => assert!(retry_in > Duration::from_secs(55) && retry_in <= Duration::from_secs(60)),
+            other => panic!("expected RateLimited, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,393 @@
+mod guard;
+mod retry;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use reqwest::{header, RequestBuilder, Response};
+use serde_json::Value;
+use tracing::{instrument, warn};
+
+use crate::domain::{SpaceSource, UpstreamHealth};
+use guard::HostGuard;
+
+pub use guard::{GuardConfig, GuardError};
+pub use retry::RetryPolicy;
+
+/// Настройки одного апстрима: базовый URL, таймаут, User-Agent и (опционально) ключ API
+#[derive(Clone, Debug)]
+pub struct UpstreamConfig {
+    pub base_url: String,
+    pub timeout: Duration,
+    pub user_agent: String,
+    pub api_key: Option<String>,
+    pub retry: RetryPolicy,
+    /// Переопределения политики для отдельных источников одного апстрима (apod/neo/donki у api.nasa.gov)
+    pub source_retry: HashMap<String, RetryPolicy>,
+    /// Лимит и breaker действуют на хост: клиенты с общим хостом делят одну корзину
+    pub guard: GuardConfig,
+}
+
+impl UpstreamConfig {
+    pub fn new(base_url: impl Into<String>, timeout: Duration, user_agent: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(), timeout, user_agent: user_agent.into(), api_key: None,
+            retry: RetryPolicy::default(), source_retry: HashMap::new(), guard: GuardConfig::default(),
+        }
+    }
+
+    pub fn with_guard(mut self, guard: GuardConfig) -> Self {
+        self.guard = guard;
+        self
+    }
+
+    fn host(&self) -> String {
+        reqwest::Url::parse(&self.base_url).ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_else(|| self.base_url.clone())
+    }
+
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    pub fn with_source_retry(mut self, source: &str, policy: RetryPolicy) -> Self {
+        self.source_retry.insert(source.to_string(), policy);
+        self
+    }
+
+    fn retry_for(&self, source: &str) -> &RetryPolicy {
+        self.source_retry.get(source).unwrap_or(&self.retry)
+    }
+
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        let key = key.into();
+        self.api_key = if key.is_empty() { None } else { Some(key) };
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        if path.is_empty() {
+            return self.base_url.clone();
+        }
+        format!("{}/{}", self.base_url.trim_end_matches('/'), path.trim_start_matches('/'))
+    }
+}
+
+/// Общая часть всех клиентов: один пул соединений на весь процесс
+#[derive(Clone)]
+struct Upstream {
+    http: reqwest::Client,
+    cfg: UpstreamConfig,
+    guard: Arc<HostGuard>,
+}
+
+impl Upstream {
+    fn get(&self, path: &str) -> RequestBuilder {
+        let mut req = self.http.get(self.cfg.url(path))
+            .timeout(self.cfg.timeout)
+            .header(header::USER_AGENT, &self.cfg.user_agent);
+        if let Some(k) = &self.cfg.api_key {
+            req = req.query(&[("api_key", k)]);
+        }
+        req
+    }
+
+    async fn json(&self, source: &str, req: RequestBuilder) -> anyhow::Result<Value> {
+        let resp = self.send(source, req).await?;
+        resp.json().await.map_err(|e| e.without_url().into())
+    }
+
+    async fn text(&self, source: &str, req: RequestBuilder) -> anyhow::Result<String> {
+        let resp = self.send(source, req).await?;
+        resp.text().await.map_err(|e| e.without_url().into())
+    }
+
+    /// Логический вызов апстрима: breaker -> повторы (каждая попытка тратит токен) -> учёт результата.
+    /// Если future бросят посреди повторов, пропуск breaker'а освободится сам.
+    async fn send(&self, source: &str, req: RequestBuilder) -> anyhow::Result<Response> {
+        let admission = self.guard.admit()?;
+        let res = self.send_with_retry(source, req).await;
+        match &res {
+            Ok(_) => admission.success(),
+            // до апстрима не дошли (кончились токены) — слот пробы отпускает Drop
+            Err(e) if e.downcast_ref::<GuardError>().is_some() => drop(admission),
+            Err(e) if Self::is_outage(e) => admission.failure(),
+            // 4xx кроме 429 — апстрим жив, просто ответил отказом
+            Err(_) => admission.success(),
+        }
+        // URL запроса содержит api_key — в логи, job_runs и ответы API он попадать не должен
+        res.map_err(|e| match e.downcast::<reqwest::Error>() {
+            Ok(re) => re.without_url().into(),
+            Err(e) => e,
+        })
+    }
+
+    fn is_outage(e: &anyhow::Error) -> bool {
+        match e.downcast_ref::<reqwest::Error>() {
+            Some(re) => match re.status() {
+                Some(s) => s.is_server_error() || s.as_u16() == 429,
+                None => re.is_timeout() || re.is_connect() || re.is_request(),
+            },
+            None => false,
+        }
+    }
+
+    /// GET с повторами по политике источника; учитывает `Retry-After`
+    async fn send_with_retry(&self, source: &str, req: RequestBuilder) -> anyhow::Result<Response> {
+        let policy = self.cfg.retry_for(source);
+        let mut attempt = 1;
+        loop {
+            let last = attempt >= policy.max_attempts;
+            self.guard.take_token()?;
+            let this_try = req.try_clone().ok_or_else(|| anyhow::anyhow!("request body is not cloneable"))?;
+            let delay = match this_try.send().await {
+                Ok(resp) if resp.status().is_success() => return Ok(resp),
+                Ok(resp) => {
+                    let status = resp.status();
+                    let wait = retry::retry_after(resp.headers());
+                    let err = resp.error_for_status().expect_err("non-success status");
+                    if last || !policy.is_retryable_status(status) {
+                        return Err(err.into());
+                    }
+                    match wait {
+                        // апстрим просит ждать дольше, чем мы готовы — не долбим его
+                        Some(w) if w > policy.max_delay => return Err(err.into()),
+                        Some(w) => w,
+                        None => policy.backoff(attempt),
+                    }
+                }
+                Err(e) => {
+                    if last || !policy.is_retryable_error(&e) {
+                        return Err(e.into());
+                    }
+                    policy.backoff(attempt)
+                }
+            };
+            warn!(source, attempt, delay_ms = delay.as_millis() as u64, "upstream call failed, retrying");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// wheretheiss.at — текущая позиция МКС
+#[derive(Clone)]
+pub struct WhereIssClient(Upstream);
+
+impl WhereIssClient {
+    pub fn url(&self) -> &str {
+        &self.0.cfg.base_url
+    }
+
+    #[instrument(skip_all, name = "client.where_iss.position")]
+    pub async fn position(&self) -> anyhow::Result<Value> {
+        self.0.json("iss", self.0.get("")).await
+    }
+}
+
+/// NASA OSDR biodata API
+#[derive(Clone)]
+pub struct OsdrClient(Upstream);
+
+impl OsdrClient {
+    #[instrument(skip_all, name = "client.osdr.datasets")]
+    pub async fn datasets(&self) -> anyhow::Result<Value> {
+        self.0.json("osdr", self.0.get("")).await
+    }
+}
+
+/// api.nasa.gov — APOD, NeoWs, DONKI (общая квота на ключ)
+#[derive(Clone)]
+pub struct NasaApiClient(Upstream);
+
+impl NasaApiClient {
+    #[instrument(skip_all, name = "client.nasa.apod")]
+    pub async fn apod(&self) -> anyhow::Result<Value> {
+        self.0.json("apod", self.0.get("planetary/apod").query(&[("thumbs", "true")])).await
+    }
+
+    #[instrument(skip_all, name = "client.nasa.neo_feed")]
+    pub async fn neo_feed(&self, start: &str, end: &str) -> anyhow::Result<Value> {
+        self.0.json("neo", self.0.get("neo/rest/v1/feed").query(&[("start_date", start), ("end_date", end)])).await
+    }
+
+    /// DONKI: `kind` = "FLR" | "CME"
+    #[instrument(skip_all, name = "client.nasa.donki", fields(kind = kind))]
+    pub async fn donki(&self, kind: &str, from: &str, to: &str) -> anyhow::Result<Value> {
+        self.0.json("donki", self.0.get(&format!("DONKI/{kind}")).query(&[("startDate", from), ("endDate", to)])).await
+    }
+}
+
+/// SpaceX API v4
+#[derive(Clone)]
+pub struct SpaceXClient(Upstream);
+
+impl SpaceXClient {
+    #[instrument(skip_all, name = "client.spacex.next_launch")]
+    pub async fn next_launch(&self) -> anyhow::Result<Value> {
+        self.0.json("spacex", self.0.get("launches/next")).await
+    }
+}
+
+/// Источник TLE (по умолчанию CelesTrak GP, формат 3LE/2LE текстом)
+#[derive(Clone)]
+pub struct TleClient(Upstream);
+
+impl TleClient {
+    #[instrument(skip_all, name = "client.tle.fetch")]
+    pub async fn fetch(&self) -> anyhow::Result<String> {
+        self.0.text("tle", self.0.get("")).await
+    }
+}
+
+/// Все апстрим-клиенты; собираются один раз при старте и живут в `AppState`
+#[derive(Clone)]
+pub struct Clients {
+    pub where_iss: WhereIssClient,
+    pub osdr: OsdrClient,
+    pub nasa: NasaApiClient,
+    pub spacex: SpaceXClient,
+    pub tle: TleClient,
+}
+
+pub struct ClientsConfig {
+    pub where_iss: UpstreamConfig,
+    pub osdr: UpstreamConfig,
+    pub nasa: UpstreamConfig,
+    pub spacex: UpstreamConfig,
+    pub tle: UpstreamConfig,
+}
+
+impl Clients {
+    pub fn new(cfg: ClientsConfig) -> anyhow::Result<Self> {
+        let http = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .pool_idle_timeout(Duration::from_secs(90))
+            .build()?;
+        let mut guards: HashMap<String, Arc<HostGuard>> = HashMap::new();
+        let mut up = |c: UpstreamConfig| {
+            let host = c.host();
+            let guard = guards.entry(host.clone())
+                .or_insert_with(|| Arc::new(HostGuard::new(host, c.guard.clone())))
+                .clone();
+            Upstream { http: http.clone(), cfg: c, guard }
+        };
+        Ok(Self {
+            where_iss: WhereIssClient(up(cfg.where_iss)),
+            osdr: OsdrClient(up(cfg.osdr)),
+            nasa: NasaApiClient(up(cfg.nasa)),
+            spacex: SpaceXClient(up(cfg.spacex)),
+            tle: TleClient(up(cfg.tle)),
+        })
+    }
+
+    fn space_upstream(&self, src: SpaceSource) -> &Upstream {
+        match src {
+            SpaceSource::Spacex => &self.spacex.0,
+            _ => &self.nasa.0,
+        }
+    }
+
+    /// URL ресурса источника без ключа API и параметров запроса
+    pub fn space_url(&self, src: SpaceSource) -> String {
+        self.space_upstream(src).cfg.url(src.path())
+    }
+
+    pub fn space_health(&self, src: SpaceSource) -> UpstreamHealth {
+        self.space_upstream(src).guard.health()
+    }
+
+    /// Состояние лимитов и breaker'ов по хостам (для `/health`)
+    pub fn health(&self) -> Vec<UpstreamHealth> {
+        let mut seen = HashMap::new();
+        for g in [&self.where_iss.0.guard, &self.osdr.0.guard, &self.nasa.0.guard, &self.spacex.0.guard, &self.tle.0.guard] {
+            seen.entry(Arc::as_ptr(g)).or_insert_with(|| g.health());
+        }
+        let mut out: Vec<_> = seen.into_values().collect();
+        out.sort_by(|a, b| a.host.cmp(&b.host));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Reply};
+    use std::sync::atomic::Ordering;
+
+    fn upstream(base_url: &str) -> Upstream {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let cfg = UpstreamConfig::new(base_url, Duration::from_secs(5), "rust_iss-test").with_retry(policy).with_api_key("secret-key");
+        let guard = Arc::new(HostGuard::new(cfg.host(), cfg.guard.clone()));
+        Upstream { http: reqwest::Client::new(), cfg, guard }
+    }
+
+    fn status_of(e: &anyhow::Error) -> Option<u16> {
+        e.downcast_ref::<reqwest::Error>().and_then(|e| e.status()).map(|s| s.as_u16())
+    }
+
+    #[tokio::test]
+    async fn retries_5xx_until_success() {
+        let (url, hits) = testing::upstream(vec![Reply::status(503), Reply::status(502), Reply::status(200)]).await;
+        let up = upstream(&url);
+        let v = up.json("test", up.get("")).await.unwrap();
+        assert_eq!(v["status"], 200);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (url, hits) = testing::upstream(vec![Reply::status(500)]).await;
+        let up = upstream(&url);
+        let e = up.json("test", up.get("")).await.unwrap_err();
+        assert_eq!(status_of(&e), Some(500));
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_429_after_retry_after() {
+        let (url, hits) = testing::upstream(vec![Reply::status(429).retry_after("0"), Reply::status(200)]).await;
+        let up = upstream(&url);
+        up.json("test", up.get("")).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_other_4xx() {
+        let (url, hits) = testing::upstream(vec![Reply::status(404), Reply::status(200)]).await;
+        let up = upstream(&url);
+        let e = up.json("test", up.get("")).await.unwrap_err();
+        assert_eq!(status_of(&e), Some(404));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        // ключ из query не должен попасть в текст ошибки
+        assert!(!format!("{e:#}").contains("secret-key"), "{e:#}");
+    }
+
+    #[tokio::test]
+    async fn long_retry_after_aborts_without_sleeping() {
+        let (url, hits) = testing::upstream(vec![Reply::status(503).retry_after("120"), Reply::status(200)]).await;
+        let up = upstream(&url);
+        let t0 = std::time::Instant::now();
+        let e = up.json("test", up.get("")).await.unwrap_err();
+        assert_eq!(status_of(&e), Some(503));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        assert!(t0.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn source_policy_overrides_attempts() {
+        let (url, hits) = testing::upstream(vec![Reply::status(500)]).await;
+        let mut up = upstream(&url);
+        let once = RetryPolicy { max_attempts: 1, ..up.cfg.retry.clone() };
+        up.cfg = up.cfg.with_source_retry("once", once);
+        up.json("once", up.get("")).await.unwrap_err();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,109 @@
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::{header::{HeaderMap, RETRY_AFTER}, StatusCode};
+
+/// Политика повторов для одного источника (iss, osdr, apod, neo, donki, spacex)
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub retry_statuses: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            retry_statuses: vec![408, 425, 429, 500, 502, 503, 504],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn is_retryable_status(&self, status: StatusCode) -> bool {
+        self.retry_statuses.contains(&status.as_u16())
+    }
+
+    /// Таймауты и обрывы соединения повторяем, ошибки разбора ответа — нет
+    pub fn is_retryable_error(&self, e: &reqwest::Error) -> bool {
+        if let Some(s) = e.status() {
+            return self.is_retryable_status(s);
+        }
+        e.is_timeout() || e.is_connect() || e.is_request()
+    }
+
+    /// Экспоненциальная задержка с full jitter: rand(0 ..= min(max, base * 2^(attempt-1)))
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self.base_delay.saturating_mul(1u32 << attempt.saturating_sub(1).min(16));
+        let cap = exp.min(self.max_delay);
+        let ms = cap.as_millis() as u64;
+        Duration::from_millis(if ms == 0 { 0 } else { rand::thread_rng().gen_range(0..=ms) })
+    }
+}
+
+/// `Retry-After` бывает в секундах или HTTP-датой
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let v = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = v.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(v).ok()?.with_timezone(&Utc);
+    Some((at - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(retry_after: &str) -> HeaderMap {
+        let mut h = HeaderMap::new();
+        h.insert(RETRY_AFTER, HeaderValue::from_str(retry_after).unwrap());
+        h
+    }
+
+    #[test]
+    fn backoff_stays_within_exponential_cap() {
+        let p = RetryPolicy { base_delay: Duration::from_millis(100), max_delay: Duration::from_millis(1000), ..Default::default() };
+        for attempt in 1..=8 {
+            let cap = Duration::from_millis(100 * (1 << (attempt - 1))).min(p.max_delay);
+            for _ in 0..200 {
+                assert!(p.backoff(attempt) <= cap, "attempt {attempt}");
+            }
+        }
+        // огромный номер попытки не переполняет сдвиг
+        assert!(p.backoff(u32::MAX) <= p.max_delay);
+        let zero = RetryPolicy { base_delay: Duration::ZERO, ..Default::default() };
+        assert_eq!(zero.backoff(3), Duration::ZERO);
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        assert_eq!(retry_after(&headers("120")), Some(Duration::from_secs(120)));
+        assert_eq!(retry_after(&headers(" 0 ")), Some(Duration::ZERO));
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+        assert_eq!(retry_after(&headers("soon")), None);
+    }
+
+    #[test]
+    fn retry_after_http_date() {
+        let at = Utc::now() + chrono::Duration::seconds(90);
+        let wait = retry_after(&headers(&at.to_rfc2822())).unwrap();
+        assert!(wait > Duration::from_secs(85) && wait <= Duration::from_secs(90), "{wait:?}");
+        // дата в прошлом — можно повторять сразу
+        assert_eq!(retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retryable_statuses() {
+        let p = RetryPolicy::default();
+        assert!(p.is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(p.is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!p.is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(!p.is_retryable_status(StatusCode::FORBIDDEN));
+    }
+}
@@ -1,19 +1,48 @@
+mod settings;
+
 use sqlx::PgPool;
+use std::sync::Arc;
+use crate::clients::Clients;
+use crate::live::LiveFeed;
+use crate::orbit::passes::PassCache;
+use crate::repositories::Repos;
+use crate::scheduler::JobRegistry;
+use crate::services::refresh::Refresher;
+
+pub use settings::{parse_duration, Settings};
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: PgPool,
-    pub nasa_url: String,
-    pub nasa_key: String,
-    pub fallback_url: String,
-    pub every_osdr: u64,
-    pub every_iss: u64,
-    pub every_apod: u64,
-    pub every_neo: u64,
-    pub every_donki: u64,
-    pub every_spacex: u64,
+    pub repos: Repos,
+    pub clients: Clients,
+    pub jobs: Arc<JobRegistry>,
+    pub settings: Arc<Settings>,
+    pub passes: Arc<PassCache>,
+    pub live: Arc<LiveFeed>,
+    pub refresh: Arc<Refresher>,
 }
 
-pub fn env_u64(k: &str, d: u64) -> u64 {
-    std::env::var(k).ok().and_then(|s| s.parse().ok()).unwrap_or(d)
-}
\ No newline at end of file
+#[cfg(test)]
+impl AppState {
+    /// Репозитории в памяти и зарегистрированные задачи; `vars` — переменные настроек поверх умолчаний.
+    /// Пул ленивый и никуда не подключается: Postgres нужен только слушателю NOTIFY.
+    pub async fn in_memory(vars: &[(&str, &str)]) -> Self {
+        let mut all = vec![("DATABASE_URL", "postgres://test@127.0.0.1:1/unused")];
+        all.extend_from_slice(vars);
+        let settings = Arc::new(Settings::from_vars(&all).expect("test settings"));
+        let repos = Repos::in_memory();
+        let jobs = Arc::new(crate::scheduler::jobs::default_registry(&settings));
+        jobs.restore(&*repos.jobs).await.expect("register jobs");
+        Self {
+            pool: sqlx::postgres::PgPoolOptions::new().connect_lazy(&settings.database_url).expect("lazy pool"),
+            repos,
+            clients: Clients::new(settings.clients_config()).expect("clients"),
+            jobs,
+            settings,
+            passes: Arc::default(),
+            live: Arc::default(),
+            refresh: Arc::default(),
+        }
+    }
+}
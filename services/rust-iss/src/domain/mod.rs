@@ -6,6 +6,18 @@ use serde_json::Value;
 pub struct Health {
     pub status: &'static str,
     pub now: DateTime<Utc>,
+    pub upstreams: Vec<UpstreamHealth>,
+}
+
+#[derive(Serialize)]
+pub struct UpstreamHealth {
+    pub host: String,
+    /// closed | open | half_open
+    pub circuit: &'static str,
+    pub consecutive_failures: u32,
+    pub open_until: Option<DateTime<Utc>>,
+    pub tokens_available: u32,
+    pub rate_per_hour: u32,
 }
 
 #[derive(Serialize)]
@@ -20,11 +32,426 @@ pub struct Trend {
     pub from_lon: Option<f64>,
     pub to_lat: Option<f64>,
     pub to_lon: Option<f64>,
+    pub window: TrendWindow,
+    /// Последние измерения окна для графиков — только при `?limit=`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub points: Option<Vec<TrendPoint>>,
+}
+
+/// Статистика по измеренным (`observed`) позициям за окно `?window=`
+#[derive(Serialize)]
+pub struct TrendWindow {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub seconds: i64,
+    /// `raw` — по каждому измерению; `hour`/`day` — по агрегатам (без скорости по земле)
+    pub resolution: Resolution,
+    pub samples: usize,
+    /// Позиции по SGP4, записанные вместо недоступного апстрима; в статистику не входят
+    pub predicted_samples: usize,
+    pub velocity_kmh: Option<Stats>,
+    pub altitude_km: Option<Stats>,
+    /// Скорость подспутниковой точки по соседним измерениям (haversine / dt), без пар через пропуск
+    pub ground_speed_kmh: Option<Stats>,
+    /// Интервал между измерениями, после которого он считается пропуском
+    pub gap_threshold_sec: f64,
+    pub gaps: Vec<DataGap>,
+    pub orbits: Option<OrbitEstimate>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DataGap {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub seconds: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct OrbitEstimate {
+    /// Период круговой орбиты по средним высоте и скорости: 2π(R+h)/v
+    pub period_min: f64,
+    /// Длина окна в периодах
+    pub estimated: f64,
+    /// Фактически замеченные восходящие узлы (переход широты через 0 к северу); по агрегатам не считается
+    pub ascending_node_crossings: Option<u32>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TrendPoint {
+    pub lat: f64,
+    pub lon: f64,
+    pub at: DateTime<Utc>,
+    pub velocity: Option<f64>,
+    pub altitude: Option<f64>,
+}
+
+impl From<&IssPosition> for TrendPoint {
+    fn from(p: &IssPosition) -> Self {
+        Self { lat: p.lat, lon: p.lon, at: p.timestamp, velocity: p.velocity_kmh, altitude: p.altitude_km }
+    }
+}
+
+/// Детализация истории позиций: сырые строки `iss_fetch_log` или агрегаты `iss_rollup_{hourly,daily}`
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    Raw,
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Resolution::Raw => "raw",
+            Resolution::Hour => "hour",
+            Resolution::Day => "day",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "raw" => Some(Resolution::Raw),
+            "hour" => Some(Resolution::Hour),
+            "day" => Some(Resolution::Day),
+            _ => None,
+        }
+    }
+
+    pub fn bucket_seconds(self) -> i64 {
+        match self {
+            Resolution::Raw => 0,
+            Resolution::Hour => 3600,
+            Resolution::Day => 86400,
+        }
+    }
+}
+
+/// Источник снимков `space_cache`; `as_str` — значение колонки `source` и сегмент `/space/:src`
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SpaceSource {
+    Apod,
+    Neo,
+    Flr,
+    Cme,
+    Spacex,
+}
+
+impl SpaceSource {
+    pub const ALL: [SpaceSource; 5] = [SpaceSource::Apod, SpaceSource::Neo, SpaceSource::Flr, SpaceSource::Cme, SpaceSource::Spacex];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SpaceSource::Apod => "apod",
+            SpaceSource::Neo => "neo",
+            SpaceSource::Flr => "flr",
+            SpaceSource::Cme => "cme",
+            SpaceSource::Spacex => "spacex",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.as_str() == s)
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            SpaceSource::Apod => "NASA Astronomy Picture of the Day",
+            SpaceSource::Neo => "NASA NeoWs near-Earth objects feed for the last 2 days",
+            SpaceSource::Flr => "NASA DONKI solar flares for the last 5 days",
+            SpaceSource::Cme => "NASA DONKI coronal mass ejections for the last 5 days",
+            SpaceSource::Spacex => "SpaceX next launch",
+        }
+    }
+
+    /// Фоновая задача, которая его обновляет: обе ленты DONKI — в одной `donki`
+    pub fn job(self) -> &'static str {
+        match self {
+            SpaceSource::Apod => "apod",
+            SpaceSource::Neo => "neo",
+            SpaceSource::Flr | SpaceSource::Cme => "donki",
+            SpaceSource::Spacex => "spacex",
+        }
+    }
+
+    /// Путь ресурса относительно базового URL апстрима (NASA API или SpaceX API)
+    pub fn path(self) -> &'static str {
+        match self {
+            SpaceSource::Apod => "planetary/apod",
+            SpaceSource::Neo => "neo/rest/v1/feed",
+            SpaceSource::Flr => "DONKI/FLR",
+            SpaceSource::Cme => "DONKI/CME",
+            SpaceSource::Spacex => "launches/next",
+        }
+    }
+}
+
+/// Агрегат за час/сутки: статистика по observed-позициям и первая точка корзины
+#[derive(Serialize, Clone, Debug)]
+pub struct IssRollup {
+    pub bucket: DateTime<Utc>,
+    pub samples: i64,
+    pub predicted_samples: i64,
+    pub altitude_km: Option<Stats>,
+    pub velocity_kmh: Option<Stats>,
+    pub first_at: DateTime<Utc>,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl From<&IssRollup> for TrackPoint {
+    fn from(r: &IssRollup) -> Self {
+        Self {
+            lat: r.lat,
+            lon: r.lon,
+            at: r.first_at,
+            altitude_km: r.altitude_km.map(|s| s.avg),
+            velocity_kmh: r.velocity_kmh.map(|s| s.avg),
+            source: if r.samples > 0 { PositionSource::Observed } else { PositionSource::Predicted },
+        }
+    }
+}
+
+impl From<&IssRollup> for TrendPoint {
+    fn from(r: &IssRollup) -> Self {
+        Self { lat: r.lat, lon: r.lon, at: r.first_at, velocity: r.velocity_kmh.map(|s| s.avg), altitude: r.altitude_km.map(|s| s.avg) }
+    }
+}
+
+/// Итог прогона задачи `iss_rollup`
+#[derive(Serialize, Default, Debug)]
+pub struct RetentionReport {
+    pub partitions_created: Vec<String>,
+    pub hourly_buckets: u64,
+    pub daily_buckets: u64,
+    pub partitions_dropped: Vec<String>,
+    pub raw_rows_deleted: u64,
+    pub hourly_rows_deleted: u64,
+}
+
+/// Откуда позиция: измерена апстримом или посчитана SGP4 по TLE
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PositionSource {
+    Observed,
+    Predicted,
+}
+
+impl PositionSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Observed => "observed",
+            Self::Predicted => "predicted",
+        }
+    }
+
+    pub fn parse(s: &str) -> Self {
+        if s == "predicted" { Self::Predicted } else { Self::Observed }
+    }
+}
+
+/// Позиция МКС, разобранная из ответа wheretheiss.at при записи
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct IssPosition {
+    pub lat: f64,
+    pub lon: f64,
+    pub altitude_km: Option<f64>,
+    pub velocity_kmh: Option<f64>,
+    /// daylight | eclipsed
+    pub visibility: Option<String>,
+    pub footprint_km: Option<f64>,
+    pub solar_lat: Option<f64>,
+    pub solar_lon: Option<f64>,
+    /// Момент измерения по данным апстрима (без него — время запроса)
+    pub timestamp: DateTime<Utc>,
+    pub source: PositionSource,
+}
+
+impl IssPosition {
+    /// Координаты обязательны, остальные поля — если есть. Числа бывают и строками.
+    pub fn from_wheretheiss(v: &Value, fetched_at: DateTime<Utc>) -> anyhow::Result<Self> {
+        let f = |k: &str| -> Option<f64> {
+            let x = v.get(k)?;
+            x.as_f64().or_else(|| x.as_str()?.trim().parse().ok())
+        };
+        let (Some(lat), Some(lon)) = (f("latitude"), f("longitude")) else {
+            anyhow::bail!("wheretheiss payload has no latitude/longitude");
+        };
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            anyhow::bail!("wheretheiss position out of range: {lat}, {lon}");
+        }
+        Ok(Self {
+            lat,
+            lon,
+            altitude_km: f("altitude"),
+            velocity_kmh: f("velocity"),
+            visibility: v.get("visibility").and_then(|x| x.as_str()).map(str::to_string),
+            footprint_km: f("footprint"),
+            solar_lat: f("solar_lat"),
+            solar_lon: f("solar_lon"),
+            timestamp: f("timestamp").and_then(|t| DateTime::from_timestamp(t as i64, 0)).unwrap_or(fetched_at),
+            source: PositionSource::Observed,
+        })
+    }
+}
+
+/// Точка наземного трека МКС
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TrackPoint {
+    pub lat: f64,
+    pub lon: f64,
+    pub at: DateTime<Utc>,
+    pub altitude_km: Option<f64>,
+    pub velocity_kmh: Option<f64>,
+    pub source: PositionSource,
+}
+
+impl From<&IssPosition> for TrackPoint {
+    fn from(p: &IssPosition) -> Self {
+        Self { lat: p.lat, lon: p.lon, at: p.timestamp, altitude_km: p.altitude_km, velocity_kmh: p.velocity_kmh, source: p.source }
+    }
+}
+
+/// Трек за интервал: отрезки, разрезанные по антимеридиану
+#[derive(Serialize)]
+pub struct IssTrack {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub resolution: Resolution,
+    pub max_points: usize,
+    /// Сколько точек отдано после прореживания (без точек на антимеридиане)
+    pub points: usize,
+    pub segments: Vec<Vec<TrackPoint>>,
+}
+
+/// Положение МКС на небе наблюдателя
+#[derive(Serialize, Clone, Debug)]
+pub struct PassPoint {
+    pub at: DateTime<Utc>,
+    /// От севера по часовой стрелке
+    pub azimuth_deg: f64,
+    pub elevation_deg: f64,
+}
+
+/// Пролёт над горизонтом наблюдателя
+#[derive(Serialize, Clone, Debug)]
+pub struct IssPass {
+    pub rise: PassPoint,
+    pub culmination: PassPoint,
+    pub set: PassPoint,
+    pub max_elevation_deg: f64,
+    pub duration_s: i64,
+    /// МКС освещена Солнцем, а у наблюдателя темно — видна глазом
+    pub visible: bool,
+    /// От наблюдателя до подспутниковой точки в кульминации
+    pub min_ground_distance_km: f64,
+}
+
+/// Строка `iss_fetch_log`: сырой ответ + разобранная позиция (у старых нераспознанных строк её нет)
+#[derive(Clone, Debug)]
+pub struct IssFetch {
+    pub id: i64,
+    pub fetched_at: DateTime<Utc>,
+    pub source_url: String,
+    pub payload: Value,
+    pub position: Option<IssPosition>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SpaceCacheItem {
+    pub id: i64,
     pub source: String,
+    /// Когда снимок с таким содержимым пришёл впервые
     pub fetched_at: DateTime<Utc>,
+    /// Когда апстрим последний раз отдал его же
+    pub last_seen_at: DateTime<Utc>,
     pub payload: Value,
-}
\ No newline at end of file
+}
+
+/// Версия снимка без самого содержимого — для `/space/:src/history`
+#[derive(Serialize, Clone, Debug)]
+pub struct SpaceCacheVersion {
+    pub id: i64,
+    pub fetched_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub content_hash: String,
+    pub bytes: i64,
+}
+
+/// Итог ручного обновления одного источника
+#[derive(Serialize, Clone, Debug)]
+pub struct SourceRefresh {
+    pub source: SpaceSource,
+    pub ok: bool,
+    /// Ещё выполняется; `duration_ms` — сколько уже идёт
+    pub pending: bool,
+    /// Результат чужого прогона: такого же запроса или плановой задачи, в том числе на другой реплике
+    pub joined: bool,
+    pub duration_ms: u64,
+    pub error_code: Option<String>,
+    pub error: Option<String>,
+    /// Когда впервые получена версия, которая сейчас последняя; при неизменных данных не сдвигается
+    pub fetched_at: Option<DateTime<Utc>>,
+    /// Когда апстрим последний раз отдал эту версию — после успешного обновления это его время
+    pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+/// Состояние `/space/refresh` по его id
+#[derive(Serialize, Clone, Debug)]
+pub struct RefreshStatus {
+    pub id: String,
+    pub started_at: DateTime<Utc>,
+    /// running | done
+    pub status: &'static str,
+    /// Все источники обновлены успешно
+    pub ok: bool,
+    pub refreshed: Vec<SpaceSource>,
+    pub sources: Vec<SourceRefresh>,
+}
+
+/// Сколько удалила очистка `space_cache` по одному источнику
+#[derive(Serialize, Default, Clone, Debug)]
+pub struct CachePruned {
+    pub source: String,
+    pub rows: u64,
+    /// `pg_column_size(payload)` удалённых строк
+    pub bytes: u64,
+}
+
+/// Состояние `space_cache` по источнику и накопленные метрики дедупликации и очистки
+#[derive(Serialize, Clone, Debug)]
+pub struct CacheSourceStats {
+    pub source: String,
+    pub versions: i64,
+    pub bytes: i64,
+    pub oldest: Option<DateTime<Utc>>,
+    pub newest: Option<DateTime<Utc>>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    /// Сколько раз апстрим отдал то же, что уже лежит последней версией
+    pub dedup_hits: i64,
+    pub rows_pruned: i64,
+    pub bytes_reclaimed: i64,
+    pub last_pruned_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct JobStatus {
+    pub name: String,
+    pub interval_seconds: i64,
+    pub paused: bool,
+    /// Выполняется ли сейчас в этом процессе
+    pub running: bool,
+    pub run_count: i64,
+    pub last_started: Option<DateTime<Utc>>,
+    pub last_finished: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
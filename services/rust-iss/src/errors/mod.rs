@@ -0,0 +1,252 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::json;
+use tracing::error;
+use crate::clients::GuardError;
+use crate::domain::SpaceSource;
+use crate::middleware::current_request_id;
+
+/// Ошибка API со стабильным кодом для клиента (Laravel ветвится по `ok` и `error.code`).
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    /// Апстрим ответил не-2xx статусом -> `UPSTREAM_<status>`
+    #[error("upstream responded with status {status}")]
+    UpstreamStatus { status: u16, message: String },
+    #[error("upstream timeout: {0}")]
+    UpstreamTimeout(String),
+    #[error("upstream unavailable: {0}")]
+    UpstreamUnavailable(String),
+    #[error("{0}")]
+    CircuitOpen(String),
+    #[error("{0}")]
+    RateLimited(String),
+    #[error("database error: {0}")]
+    Db(String),
+    #[error("{0}")]
+    Validation(String),
+    #[error("{0}")]
+    NotFound(String),
+    /// `:src` / `?src=` не из [`SpaceSource::ALL`]
+    #[error("unknown source {0:?}; expected one of {known}", known = SpaceSource::ALL.map(SpaceSource::as_str).join(", "))]
+    UnknownSource(String),
+    #[error("job {0} is already running")]
+    JobRunning(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn validation(msg: impl Into<String>) -> Self {
+        ApiError::Validation(msg.into())
+    }
+
+    pub fn code(&self) -> String {
+        match self {
+            ApiError::UpstreamStatus { status, .. } => format!("UPSTREAM_{status}"),
+            ApiError::UpstreamTimeout(_) => "UPSTREAM_TIMEOUT".into(),
+            ApiError::UpstreamUnavailable(_) => "UPSTREAM_UNAVAILABLE".into(),
+            ApiError::CircuitOpen(_) => "UPSTREAM_CIRCUIT_OPEN".into(),
+            ApiError::RateLimited(_) => "UPSTREAM_RATE_LIMITED".into(),
+            ApiError::Db(_) => "DB_ERROR".into(),
+            ApiError::Validation(_) => "VALIDATION_ERROR".into(),
+            ApiError::NotFound(_) => "NOT_FOUND".into(),
+            ApiError::UnknownSource(_) => "UNKNOWN_SOURCE".into(),
+            ApiError::JobRunning(_) => "JOB_RUNNING".into(),
+            ApiError::Internal(_) => "INTERNAL".into(),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::UpstreamStatus { .. } | ApiError::UpstreamUnavailable(_) => StatusCode::BAD_GATEWAY,
+            ApiError::UpstreamTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::CircuitOpen(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Db(_) | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Validation(_) | ApiError::UnknownSource(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::JobRunning(_) => StatusCode::CONFLICT,
+        }
+    }
+
+    /// Текст для клиента: без сырых деталей БД и внутренних ошибок
+    pub fn public_message(&self) -> String {
+        match self {
+            ApiError::UpstreamStatus { status, message } if message.is_empty() => format!("upstream responded with status {status}"),
+            ApiError::UpstreamStatus { message, .. } => message.clone(),
+            ApiError::Db(_) => "database error".into(),
+            ApiError::Internal(_) => "internal error".into(),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            ApiError::UpstreamTimeout(e.to_string())
+        } else if let Some(s) = e.status() {
+            ApiError::UpstreamStatus { status: s.as_u16(), message: String::new() }
+        } else if e.is_decode() {
+            ApiError::UpstreamUnavailable(format!("bad upstream payload: {e}"))
+        } else {
+            ApiError::UpstreamUnavailable(e.to_string())
+        }
+    }
+}
+
+impl From<GuardError> for ApiError {
+    fn from(e: GuardError) -> Self {
+        match e {
+            GuardError::CircuitOpen { .. } => ApiError::CircuitOpen(e.to_string()),
+            GuardError::RateLimited { .. } => ApiError::RateLimited(e.to_string()),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        ApiError::Db(e.to_string())
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        // сервисы возвращают anyhow — достаём исходную причину
+        let e = match e.downcast::<ApiError>() {
+            Ok(api) => return api,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<GuardError>() {
+            Ok(ge) => return ge.into(),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<reqwest::Error>() {
+            Ok(re) => return re.into(),
+            Err(e) => e,
+        };
+        match e.downcast::<sqlx::Error>() {
+            Ok(se) => se.into(),
+            Err(e) => ApiError::Internal(format!("{e:#}")),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            error!("api error {}: {self}", self.code());
+        }
+        let body = json!({
+            "ok": false,
+            "error": {
+                "code": self.code(),
+                "message": self.public_message(),
+                "trace_id": current_request_id(),
+            }
+        });
+        (status, Json(body)).into_response()
+    }
+}
+
+/// Успешный ответ в конверте `{ "ok": true, "data": ... }`
+pub struct ApiOk<T>(pub T);
+
+impl<T: Serialize> IntoResponse for ApiOk<T> {
+    fn into_response(self) -> Response {
+        Json(json!({ "ok": true, "data": self.0 })).into_response()
+    }
+}
+
+pub type ApiResult<T> = Result<ApiOk<T>, ApiError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use anyhow::Context;
+    use crate::testing::{self, Reply};
+
+    async fn body(e: ApiError) -> (StatusCode, serde_json::Value) {
+        let resp = e.into_response();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[test]
+    fn codes_and_statuses_are_stable() {
+        let cases = [
+            (ApiError::UpstreamStatus { status: 403, message: String::new() }, "UPSTREAM_403", 502),
+            (ApiError::UpstreamTimeout("t".into()), "UPSTREAM_TIMEOUT", 504),
+            (ApiError::UpstreamUnavailable("u".into()), "UPSTREAM_UNAVAILABLE", 502),
+            (ApiError::CircuitOpen("c".into()), "UPSTREAM_CIRCUIT_OPEN", 503),
+            (ApiError::RateLimited("r".into()), "UPSTREAM_RATE_LIMITED", 429),
+            (ApiError::Db("d".into()), "DB_ERROR", 500),
+            (ApiError::validation("v"), "VALIDATION_ERROR", 400),
+            (ApiError::UnknownSource("mars".into()), "UNKNOWN_SOURCE", 400),
+            (ApiError::NotFound("n".into()), "NOT_FOUND", 404),
+            (ApiError::JobRunning("iss".into()), "JOB_RUNNING", 409),
+            (ApiError::Internal("i".into()), "INTERNAL", 500),
+        ];
+        for (e, code, status) in cases {
+            assert_eq!(e.code(), code);
+            assert_eq!(e.status().as_u16(), status, "{code}");
+        }
+    }
+
+    #[tokio::test]
+    async fn envelope_hides_db_and_internal_details() {
+        let (status, v) = body(ApiError::Db("relation \"secret\" does not exist".into())).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(v["ok"], false);
+        assert_eq!(v["error"]["code"], "DB_ERROR");
+        assert_eq!(v["error"]["message"], "database error");
+        assert!(v["error"].as_object().unwrap().contains_key("trace_id"));
+
+        let (_, v) = body(ApiError::validation("limit must be > 0")).await;
+        assert_eq!(v["error"]["message"], "limit must be > 0");
+    }
+
+    #[test]
+    fn anyhow_keeps_api_and_db_errors_through_context() {
+        let e: ApiError = anyhow::Error::from(ApiError::NotFound("no such row".into())).context("load").into();
+        assert_eq!(e.code(), "NOT_FOUND");
+        let open = GuardError::CircuitOpen { host: "api.nasa.gov".into(), retry_in: Duration::from_secs(5) };
+        let e: ApiError = anyhow::Error::from(open).context("fetch apod").into();
+        assert_eq!(e.code(), "UPSTREAM_CIRCUIT_OPEN");
+        let e: ApiError = anyhow::Error::from(sqlx::Error::RowNotFound).context("load").into();
+        assert_eq!(e.code(), "DB_ERROR");
+        let e: ApiError = anyhow::anyhow!("boom").into();
+        assert_eq!(e.code(), "INTERNAL");
+        assert_eq!(e.public_message(), "internal error");
+    }
+
+    #[tokio::test]
+    async fn anyhow_upstream_status_becomes_upstream_code() {
+        let (url, _) = testing::upstream(vec![Reply::status(403)]).await;
+        let res: anyhow::Result<reqwest::Response> = async {
+            Ok(reqwest::get(&url).await?.error_for_status()?)
+        }.await;
+        let e: ApiError = res.context("fetch apod").unwrap_err().into();
+        assert_eq!(e.code(), "UPSTREAM_403");
+        assert_eq!(e.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn anyhow_upstream_timeout_becomes_timeout_code() {
+        // принимает соединения (backlog ядра), но никогда не отвечает
+        let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", silent.local_addr().unwrap());
+        let client = reqwest::Client::builder().timeout(Duration::from_millis(100)).build().unwrap();
+        let e = client.get(&url).send().await.unwrap_err();
+        let e: ApiError = anyhow::Error::from(e).into();
+        assert_eq!(e.code(), "UPSTREAM_TIMEOUT");
+        assert_eq!(e.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt::Write;
+use chrono::SecondsFormat;
+use serde_json::{json, Value};
+use crate::domain::{IssTrack, TrackPoint};
+
+/// FeatureCollection: трек (LineString или MultiLineString, если пересекает антимеридиан)
+/// и по Point на каждое измерение с высотой и скоростью в properties
+pub fn geojson(track: &IssTrack, samples: &[TrackPoint]) -> Value {
+    let lines: Vec<Vec<[f64; 2]>> = track.segments.iter()
+        .map(|s| s.iter().map(|p| [p.lon, p.lat]).collect())
+        .filter(|s: &Vec<[f64; 2]>| s.len() >= 2)
+        .collect();
+    let geometry = match lines.as_slice() {
+        [] => Value::Null,
+        [one] => json!({ "type": "LineString", "coordinates": one }),
+        many => json!({ "type": "MultiLineString", "coordinates": many }),
+    };
+
+    let mut features = vec![json!({
+        "type": "Feature",
+        "geometry": geometry,
+        "properties": { "kind": "track", "from": track.from, "to": track.to, "points": track.points },
+    })];
+    features.extend(samples.iter().map(|p| json!({
+        "type": "Feature",
+        "geometry": { "type": "Point", "coordinates": [p.lon, p.lat] },
+        "properties": {
+            "kind": "position",
+            "at": p.at,
+            "altitude_km": p.altitude_km,
+            "velocity_kmh": p.velocity_kmh,
+            "source": p.source,
+        },
+    })));
+
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+/// KML 2.2: линия трека по земле (отрезки в MultiGeometry) и папка точек с TimeStamp,
+/// чтобы в Google Earth работал ползунок времени
+pub fn kml(track: &IssTrack, samples: &[TrackPoint]) -> String {
+    let mut out = String::new();
+    let _ = write!(out, r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2">
+<Document>
+<name>ISS ground track {from} — {to}</name>
+<Style id="track"><LineStyle><color>ff00a5ff</color><width>2</width></LineStyle></Style>
+<Placemark>
+<name>ISS track</name>
+<styleUrl>#track</styleUrl>
+<MultiGeometry>
+"#, from = track.from.to_rfc3339_opts(SecondsFormat::Secs, true), to = track.to.to_rfc3339_opts(SecondsFormat::Secs, true));
+    for seg in track.segments.iter().filter(|s| s.len() >= 2) {
+        out.push_str("<LineString><tessellate>1</tessellate><coordinates>");
+        for p in seg {
+            let _ = write!(out, "{},{} ", p.lon, p.lat);
+        }
+        out.push_str("</coordinates></LineString>\n");
+    }
+    out.push_str("</MultiGeometry>\n</Placemark>\n<Folder>\n<name>Positions</name>\n");
+    for p in samples {
+        let _ = write!(out, "<Placemark><TimeStamp><when>{}</when></TimeStamp>", p.at.to_rfc3339());
+        out.push_str("<ExtendedData>");
+        if let Some(a) = p.altitude_km {
+            let _ = write!(out, r#"<Data name="altitude_km"><value>{a}</value></Data>"#);
+        }
+        if let Some(v) = p.velocity_kmh {
+            let _ = write!(out, r#"<Data name="velocity_kmh"><value>{v}</value></Data>"#);
+        }
+        let _ = write!(out, r#"<Data name="source"><value>{}</value></Data>"#, p.source.as_str());
+        let _ = writeln!(out, "</ExtendedData><Point><coordinates>{},{}</coordinates></Point></Placemark>", p.lon, p.lat);
+    }
+    out.push_str("</Folder>\n</Document>\n</kml>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, Utc};
+    use crate::domain::{PositionSource, Resolution};
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    fn pt(lon: f64, lat: f64, secs: i64) -> TrackPoint {
+        TrackPoint { lat, lon, at: t0() + Duration::seconds(secs), altitude_km: Some(420.5), velocity_kmh: Some(27600.0), source: PositionSource::Observed }
+    }
+
+    fn track(segments: Vec<Vec<TrackPoint>>) -> IssTrack {
+        let points = segments.iter().map(Vec::len).sum();
+        IssTrack { from: t0(), to: t0() + Duration::hours(1), resolution: Resolution::Raw, max_points: 100, points, segments }
+    }
+
+    /// Теги открываются и закрываются парно и в правильном порядке
+    fn assert_balanced(xml: &str) {
+        let mut stack = Vec::new();
+        for tag in xml.split('<').skip(1).map(|s| &s[..s.find('>').unwrap()]) {
+            if tag.starts_with('?') || tag.ends_with('/') {
+                continue;
+            }
+            let name = tag.split_whitespace().next().unwrap();
+            match name.strip_prefix('/') {
+                Some(close) => assert_eq!(stack.pop(), Some(close), "unbalanced </{close}>"),
+                None => stack.push(name),
+            }
+        }
+        assert!(stack.is_empty(), "unclosed tags: {stack:?}");
+    }
+
+    #[test]
+    fn geojson_single_segment_is_line_string() {
+        let t = track(vec![vec![pt(10.0, 1.0, 0), pt(20.0, 2.0, 60)]]);
+        let fc = geojson(&t, &[]);
+        let geom = &fc["features"][0]["geometry"];
+        assert_eq!(geom["type"], "LineString");
+        assert_eq!(geom["coordinates"], serde_json::json!([[10.0, 1.0], [20.0, 2.0]]));
+    }
+
+    #[test]
+    fn geojson_drops_short_segments_and_uses_multi_line_string() {
+        let t = track(vec![
+            vec![pt(170.0, 0.0, 0), pt(180.0, 1.0, 30)],
+            vec![pt(-180.0, 1.0, 30)],
+            vec![pt(-180.0, 1.0, 30), pt(-170.0, 2.0, 60)],
+        ]);
+        let geom = &geojson(&t, &[])["features"][0]["geometry"];
+        assert_eq!(geom["type"], "MultiLineString");
+        assert_eq!(geom["coordinates"].as_array().unwrap().len(), 2);
+
+        // одни одиночные точки — линии нет вовсе
+        let t = track(vec![vec![pt(0.0, 0.0, 0)]]);
+        assert!(geojson(&t, &[])["features"][0]["geometry"].is_null());
+    }
+
+    #[test]
+    fn geojson_points_carry_altitude_and_velocity() {
+        let t = track(vec![vec![pt(10.0, 1.0, 0), pt(20.0, 2.0, 60)]]);
+        let fc = geojson(&t, &[pt(10.0, 1.0, 0), pt(20.0, 2.0, 60)]);
+        let features = fc["features"].as_array().unwrap();
+        assert_eq!(features.len(), 3);
+        assert_eq!(features[0]["properties"]["kind"], "track");
+        let p = &features[2];
+        assert_eq!(p["geometry"]["type"], "Point");
+        assert_eq!(p["geometry"]["coordinates"], serde_json::json!([20.0, 2.0]));
+        assert_eq!(p["properties"]["kind"], "position");
+        assert_eq!(p["properties"]["altitude_km"], 420.5);
+        assert_eq!(p["properties"]["velocity_kmh"], 27600.0);
+        assert_eq!(p["properties"]["source"], "observed");
+    }
+
+    #[test]
+    fn kml_is_well_formed_with_line_string_per_segment() {
+        let t = track(vec![
+            vec![pt(170.0, 0.0, 0), pt(180.0, 1.0, 30)],
+            vec![pt(-180.0, 1.0, 30)],
+            vec![pt(-180.0, 1.0, 30), pt(-170.0, 2.0, 60)],
+        ]);
+        let mut no_alt = pt(-170.0, 2.0, 60);
+        no_alt.altitude_km = None;
+        let doc = kml(&t, &[pt(170.0, 0.0, 0), no_alt]);
+        assert!(doc.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?>"#));
+        assert_balanced(&doc);
+        assert_eq!(doc.matches("<LineString>").count(), 2);
+        assert_eq!(doc.matches("<Point>").count(), 2);
+        assert_eq!(doc.matches(r#"<Data name="altitude_km">"#).count(), 1);
+        assert_eq!(doc.matches(r#"<Data name="velocity_kmh">"#).count(), 2);
+        assert_eq!(doc.matches(r#"<Data name="source"><value>observed</value></Data>"#).count(), 2);
+        assert!(doc.contains("<coordinates>170,0 180,1 </coordinates>"));
+    }
+}
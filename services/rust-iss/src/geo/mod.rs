@@ -0,0 +1,117 @@
+pub mod export;
+pub mod trend;
+
+use chrono::Duration;
+use crate::domain::TrackPoint;
+
+/// Средний радиус Земли, км
+pub const EARTH_RADIUS_KM: f64 = 6371.0;
+
+pub fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let rlat1 = lat1.to_radians();
+    let rlat2 = lat2.to_radians();
+    let dlat = (lat2 - lat1).to_radians();
+    let dlon = (lon2 - lon1).to_radians();
+    let a = (dlat / 2.0).sin().powi(2) + rlat1.cos() * rlat2.cos() * (dlon / 2.0).sin().powi(2);
+    let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
+    EARTH_RADIUS_KM * c
+}
+
+/// Режет трек на отрезки там, где он пересекает ±180°, чтобы карта не рисовала линию через весь глобус.
+/// В точке пересечения добавляется интерполированная точка на обоих краях.
+pub fn split_antimeridian(points: Vec<TrackPoint>) -> Vec<Vec<TrackPoint>> {
+    let mut segments = Vec::new();
+    let mut cur: Vec<TrackPoint> = Vec::new();
+    for p in points {
+        if let Some(prev) = cur.last() {
+            if (p.lon - prev.lon).abs() > 180.0 {
+                // разворачиваем долготу p на ту же сторону, что и prev
+                let edge = if prev.lon > 0.0 { 180.0 } else { -180.0 };
+                let unwrapped = p.lon + 2.0 * edge;
+                let f = if unwrapped == prev.lon { 0.0 } else { (edge - prev.lon) / (unwrapped - prev.lon) };
+                let at = prev.at + Duration::milliseconds(((p.at - prev.at).num_milliseconds() as f64 * f) as i64);
+                let lerp = |a: Option<f64>, b: Option<f64>| Some(a? + (b? - a?) * f);
+                let cross = TrackPoint {
+                    lat: prev.lat + (p.lat - prev.lat) * f,
+                    lon: edge,
+                    at,
+                    altitude_km: lerp(prev.altitude_km, p.altitude_km),
+                    velocity_kmh: lerp(prev.velocity_kmh, p.velocity_kmh),
+                    source: prev.source,
+                };
+                let other = TrackPoint { lon: -edge, ..cross.clone() };
+                cur.push(cross);
+                segments.push(std::mem::take(&mut cur));
+                cur.push(other);
+            }
+        }
+        cur.push(p);
+    }
+    if !cur.is_empty() {
+        segments.push(cur);
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use crate::domain::PositionSource;
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    fn pt(lon: f64, lat: f64, secs: i64) -> TrackPoint {
+        TrackPoint { lat, lon, at: t0() + Duration::seconds(secs), altitude_km: Some(420.0), velocity_kmh: Some(27600.0), source: PositionSource::Observed }
+    }
+
+    #[test]
+    fn haversine_one_degree_of_latitude() {
+        assert!((haversine_km(0.0, 0.0, 1.0, 0.0) - 111.195).abs() < 0.01);
+        assert_eq!(haversine_km(51.6, 10.0, 51.6, 10.0), 0.0);
+        // через антимеридиан — короткой дугой, а не через весь глобус
+        assert!((haversine_km(0.0, 179.5, 0.0, -179.5) - 111.195).abs() < 0.01);
+    }
+
+    #[test]
+    fn track_without_crossing_stays_one_segment() {
+        let track = vec![pt(-10.0, 0.0, 0), pt(0.0, 5.0, 60), pt(10.0, 10.0, 120)];
+        assert_eq!(split_antimeridian(track.clone()), vec![track]);
+        assert!(split_antimeridian(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn eastward_crossing_splits_at_plus_180() {
+        let segs = split_antimeridian(vec![pt(160.0, 0.0, 0), pt(170.0, 10.0, 60), pt(-170.0, 20.0, 120), pt(-160.0, 30.0, 180)]);
+        assert_eq!(segs.len(), 2);
+        let (end, start) = (segs[0].last().unwrap(), &segs[1][0]);
+        assert_eq!((end.lon, start.lon), (180.0, -180.0));
+        // 170 -> -170: край ровно посередине
+        assert_eq!(end.at, t0() + Duration::seconds(90));
+        assert!((end.lat - 15.0).abs() < 1e-9);
+        assert_eq!((start.at, start.lat, start.altitude_km), (end.at, end.lat, end.altitude_km));
+        assert_eq!((segs[0].len(), segs[1].len()), (3, 3));
+        assert_eq!(segs[1][1], pt(-170.0, 20.0, 120));
+    }
+
+    #[test]
+    fn westward_crossing_splits_at_minus_180() {
+        let segs = split_antimeridian(vec![pt(-175.0, -40.0, 0), pt(175.0, -42.0, 60)]);
+        assert_eq!(segs.len(), 2);
+        let (end, start) = (segs[0].last().unwrap(), &segs[1][0]);
+        assert_eq!((end.lon, start.lon), (-180.0, 180.0));
+        assert_eq!(end.at, t0() + Duration::seconds(30));
+        assert!((end.lat + 41.0).abs() < 1e-9);
+        assert_eq!((start.at, start.lat), (end.at, end.lat));
+    }
+
+    #[test]
+    fn every_crossing_starts_new_segment() {
+        let segs = split_antimeridian(vec![pt(170.0, 0.0, 0), pt(-170.0, 1.0, 60), pt(170.0, 2.0, 120), pt(-170.0, 3.0, 180)]);
+        assert_eq!(segs.len(), 4);
+        assert!(segs.iter().all(|s| s.len() >= 2));
+        assert!(segs.iter().flatten().all(|p| (-180.0..=180.0).contains(&p.lon)));
+    }
+}
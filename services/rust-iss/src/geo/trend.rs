@@ -0,0 +1,255 @@
+use chrono::{DateTime, Utc};
+use crate::domain::{DataGap, IssPosition, IssRollup, OrbitEstimate, PositionSource, Resolution, Stats, TrendWindow};
+use super::{haversine_km, EARTH_RADIUS_KM};
+
+/// Пропуск — интервал длиннее стольких периодов опроса (запас на ретраи и дрожание планировщика)
+const GAP_FACTOR: f64 = 2.5;
+
+/// Статистика окна `[from, to]`; `positions` — по возрастанию времени измерения,
+/// `poll_interval` — период задачи `iss`, от него считается порог пропуска
+pub fn analyze(positions: &[IssPosition], from: DateTime<Utc>, to: DateTime<Utc>, poll_interval: std::time::Duration) -> TrendWindow {
+    let observed: Vec<&IssPosition> = positions.iter().filter(|p| p.source == PositionSource::Observed).collect();
+    let gap_threshold_sec = poll_interval.as_secs_f64() * GAP_FACTOR;
+
+    let mut gaps = Vec::new();
+    let mut ground_speeds = Vec::new();
+    let mut crossings = 0;
+    for w in observed.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        let dt = (b.timestamp - a.timestamp).num_milliseconds() as f64 / 1000.0;
+        if dt > gap_threshold_sec {
+            gaps.push(DataGap { from: a.timestamp, to: b.timestamp, seconds: dt });
+            continue;
+        }
+        if dt > 0.0 {
+            ground_speeds.push(haversine_km(a.lat, a.lon, b.lat, b.lon) / dt * 3600.0);
+        }
+        if a.lat < 0.0 && b.lat >= 0.0 {
+            crossings += 1;
+        }
+    }
+
+    let velocity_kmh = stats(observed.iter().filter_map(|p| p.velocity_kmh));
+    let altitude_km = stats(observed.iter().filter_map(|p| p.altitude_km));
+    let seconds = (to - from).num_seconds();
+    TrendWindow {
+        from,
+        to,
+        seconds,
+        resolution: Resolution::Raw,
+        samples: observed.len(),
+        predicted_samples: positions.len() - observed.len(),
+        velocity_kmh,
+        altitude_km,
+        ground_speed_kmh: stats(ground_speeds),
+        gap_threshold_sec,
+        gaps,
+        orbits: orbits(velocity_kmh, altitude_km, seconds, Some(crossings)),
+    }
+}
+
+/// То же по агрегатам: пропуск — корзина без observed-измерений; скорость по земле и узлы не считаются
+pub fn analyze_rollups(rollups: &[IssRollup], resolution: Resolution, from: DateTime<Utc>, to: DateTime<Utc>) -> TrendWindow {
+    let bucket = chrono::Duration::seconds(resolution.bucket_seconds());
+    let observed: Vec<&IssRollup> = rollups.iter().filter(|r| r.samples > 0).collect();
+
+    let gaps = observed.windows(2)
+        .filter_map(|w| {
+            let end = w[0].bucket + bucket;
+            (w[1].bucket > end).then(|| DataGap { from: end, to: w[1].bucket, seconds: (w[1].bucket - end).num_seconds() as f64 })
+        })
+        .collect();
+
+    let velocity_kmh = merge(observed.iter().filter_map(|r| Some((r.velocity_kmh?, r.samples))));
+    let altitude_km = merge(observed.iter().filter_map(|r| Some((r.altitude_km?, r.samples))));
+    let seconds = (to - from).num_seconds();
+    TrendWindow {
+        from,
+        to,
+        seconds,
+        resolution,
+        samples: observed.iter().map(|r| r.samples as usize).sum(),
+        predicted_samples: rollups.iter().map(|r| r.predicted_samples as usize).sum(),
+        velocity_kmh,
+        altitude_km,
+        ground_speed_kmh: None,
+        gap_threshold_sec: bucket.num_seconds() as f64,
+        gaps,
+        orbits: orbits(velocity_kmh, altitude_km, seconds, None),
+    }
+}
+
+fn orbits(velocity: Option<Stats>, altitude: Option<Stats>, seconds: i64, crossings: Option<u32>) -> Option<OrbitEstimate> {
+    velocity.zip(altitude).filter(|(v, _)| v.avg > 0.0).map(|(v, h)| {
+        let period_min = 2.0 * std::f64::consts::PI * (EARTH_RADIUS_KM + h.avg) / v.avg * 60.0;
+        OrbitEstimate { period_min, estimated: seconds as f64 / 60.0 / period_min, ascending_node_crossings: crossings }
+    })
+}
+
+pub fn stats(values: impl IntoIterator<Item = f64>) -> Option<Stats> {
+    let (mut n, mut sum, mut min, mut max) = (0usize, 0.0, f64::INFINITY, f64::NEG_INFINITY);
+    for v in values.into_iter().filter(|v| v.is_finite()) {
+        n += 1;
+        sum += v;
+        min = min.min(v);
+        max = max.max(v);
+    }
+    (n > 0).then(|| Stats { avg: sum / n as f64, min, max })
+}
+
+/// Объединение агрегатов: среднее взвешено по числу измерений
+fn merge(parts: impl IntoIterator<Item = (Stats, i64)>) -> Option<Stats> {
+    let (mut n, mut sum, mut min, mut max) = (0i64, 0.0, f64::INFINITY, f64::NEG_INFINITY);
+    for (s, w) in parts {
+        n += w;
+        sum += s.avg * w as f64;
+        min = min.min(s.min);
+        max = max.max(s.max);
+    }
+    (n > 0).then(|| Stats { avg: sum / n as f64, min, max })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    fn pos(secs: i64, lat: f64, source: PositionSource) -> IssPosition {
+        IssPosition {
+            lat,
+            lon: 10.0 + secs as f64 / 60.0 * 3.8,
+            altitude_km: Some(if source == PositionSource::Observed { 420.0 } else { 999.0 }),
+            velocity_kmh: Some(27600.0),
+            visibility: None,
+            footprint_km: None,
+            solar_lat: None,
+            solar_lon: None,
+            timestamp: t0() + Duration::seconds(secs),
+            source,
+        }
+    }
+
+    const OBS: PositionSource = PositionSource::Observed;
+    const PRED: PositionSource = PositionSource::Predicted;
+
+    #[test]
+    fn analyze_table() {
+        // (случай, позиции, измеренных, предсказанных, пропусков, восходящих узлов)
+        let cases = [
+            ("steady", vec![pos(0, -1.0, OBS), pos(60, 0.0, OBS), pos(120, 1.0, OBS)], 3, 0, 0, 1),
+            ("descending", vec![pos(0, 1.0, OBS), pos(60, -1.0, OBS)], 2, 0, 0, 0),
+            // узел внутри пропуска не засчитывается: неизвестно, сколько витков пропущено
+            ("gap", vec![pos(0, -3.0, OBS), pos(60, -2.0, OBS), pos(400, 2.0, OBS)], 3, 0, 1, 0),
+            ("predicted excluded", vec![pos(0, -1.0, OBS), pos(60, 0.0, PRED), pos(120, 1.0, OBS)], 2, 1, 0, 1),
+            ("only predicted", vec![pos(0, -1.0, PRED), pos(60, 1.0, PRED)], 0, 2, 0, 0),
+        ];
+        for (name, positions, samples, predicted, gaps, crossings) in cases {
+            let w = analyze(&positions, t0(), t0() + Duration::minutes(10), std::time::Duration::from_secs(60));
+            assert_eq!((w.samples, w.predicted_samples, w.gaps.len()), (samples, predicted, gaps), "{name}");
+            assert_eq!(w.gap_threshold_sec, 150.0, "{name}");
+            assert_eq!(w.orbits.map(|o| o.ascending_node_crossings), (samples > 0).then_some(Some(crossings)), "{name}");
+            if samples > 0 {
+                // высота 999 у предсказанных в статистику не попадает
+                assert_eq!(w.altitude_km, Some(Stats { avg: 420.0, min: 420.0, max: 420.0 }), "{name}");
+            } else {
+                assert!(w.altitude_km.is_none() && w.ground_speed_kmh.is_none(), "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn gap_bounds_and_ground_speed() {
+        let positions = [pos(0, 0.0, OBS), pos(60, 0.0, OBS), pos(400, 0.0, OBS)];
+        let w = analyze(&positions, t0(), t0() + Duration::minutes(10), std::time::Duration::from_secs(60));
+        assert_eq!((w.gaps[0].from, w.gaps[0].to, w.gaps[0].seconds), (t0() + Duration::seconds(60), t0() + Duration::seconds(400), 340.0));
+        // только пара 0–60: 3.8° по экватору за минуту
+        let gs = w.ground_speed_kmh.unwrap();
+        assert_eq!(gs.min, gs.max);
+        assert!((gs.avg - haversine_km(0.0, 10.0, 0.0, 13.8) * 60.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn orbit_period_from_altitude_and_velocity() {
+        let positions = [pos(0, 0.0, OBS), pos(60, 1.0, OBS)];
+        let w = analyze(&positions, t0(), t0() + Duration::minutes(184), std::time::Duration::from_secs(60));
+        let o = w.orbits.unwrap();
+        let period = 2.0 * std::f64::consts::PI * (EARTH_RADIUS_KM + 420.0) / 27600.0 * 60.0;
+        assert!((o.period_min - period).abs() < 1e-9);
+        assert!((o.period_min - 92.8).abs() < 0.1, "{}", o.period_min);
+        assert!((o.estimated - 184.0 / period).abs() < 1e-9);
+        assert_eq!(w.seconds, 184 * 60);
+
+        let mut still = positions.clone();
+        still.iter_mut().for_each(|p| p.velocity_kmh = Some(0.0));
+        assert!(analyze(&still, t0(), t0() + Duration::minutes(10), std::time::Duration::from_secs(60)).orbits.is_none());
+    }
+
+    fn rollup(hour: i64, samples: i64, predicted: i64, alt: (f64, f64, f64)) -> IssRollup {
+        IssRollup {
+            bucket: t0() + Duration::hours(hour),
+            samples,
+            predicted_samples: predicted,
+            altitude_km: (samples > 0).then_some(Stats { avg: alt.0, min: alt.1, max: alt.2 }),
+            velocity_kmh: (samples > 0).then_some(Stats { avg: 27600.0, min: 27500.0, max: 27700.0 }),
+            first_at: t0() + Duration::hours(hour),
+            lat: 0.0,
+            lon: 0.0,
+        }
+    }
+
+    #[test]
+    fn merge_table() {
+        let s = |avg, min, max| Stats { avg, min, max };
+        // (случай, части с весами, результат)
+        let cases = [
+            ("empty", vec![], None),
+            ("single", vec![(s(1.0, 0.0, 2.0), 3)], Some(s(1.0, 0.0, 2.0))),
+            ("weighted", vec![(s(410.0, 409.0, 411.0), 30), (s(420.0, 418.0, 425.0), 10)], Some(s(412.5, 409.0, 425.0))),
+            ("zero weight", vec![(s(100.0, 100.0, 100.0), 0)], None),
+        ];
+        for (name, parts, want) in cases {
+            assert_eq!(merge(parts), want, "{name}");
+        }
+    }
+
+    #[test]
+    fn orbits_table() {
+        let s = |avg| Some(Stats { avg, min: avg, max: avg });
+        let period = 2.0 * std::f64::consts::PI * (EARTH_RADIUS_KM + 420.0) / 27600.0 * 60.0;
+        // (случай, скорость, высота, узлы, есть ли оценка)
+        let cases = [
+            ("raw", s(27600.0), s(420.0), Some(3), true),
+            ("rollups", s(27600.0), s(420.0), None, true),
+            ("no velocity", None, s(420.0), Some(0), false),
+            ("no altitude", s(27600.0), None, Some(0), false),
+            ("zero velocity", s(0.0), s(420.0), Some(0), false),
+        ];
+        for (name, v, h, crossings, some) in cases {
+            let o = orbits(v, h, 3600, crossings);
+            assert_eq!(o.is_some(), some, "{name}");
+            if let Some(o) = o {
+                assert!((o.period_min - period).abs() < 1e-9, "{name}");
+                assert!((o.estimated - 60.0 / period).abs() < 1e-9, "{name}");
+                assert_eq!(o.ascending_node_crossings, crossings, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn rollup_window_merges_buckets_and_finds_gaps() {
+        // час 2 — только предсказанные позиции, это пропуск; часа 3 нет вовсе
+        let rollups = [rollup(0, 30, 0, (410.0, 409.0, 411.0)), rollup(1, 10, 0, (420.0, 418.0, 425.0)), rollup(2, 0, 30, (0.0, 0.0, 0.0)), rollup(4, 20, 0, (415.0, 414.0, 416.0))];
+        let w = analyze_rollups(&rollups, Resolution::Hour, t0(), t0() + Duration::hours(5));
+        assert_eq!((w.samples, w.predicted_samples), (60, 30));
+        assert_eq!(w.altitude_km, Some(Stats { avg: (410.0 * 30.0 + 420.0 * 10.0 + 415.0 * 20.0) / 60.0, min: 409.0, max: 425.0 }));
+        assert_eq!(w.gaps.len(), 1);
+        assert_eq!((w.gaps[0].from, w.gaps[0].to, w.gaps[0].seconds), (t0() + Duration::hours(2), t0() + Duration::hours(4), 7200.0));
+        assert_eq!(w.gap_threshold_sec, 3600.0);
+        assert!(w.ground_speed_kmh.is_none());
+        assert_eq!(w.orbits.unwrap().ascending_node_crossings, None);
+    }
+}
@@ -1,153 +1,667 @@
-use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    Json,
-};
+pub mod stream;
+
+use axum::extract::{FromRequestParts, Path, Query, State};
+use axum::http::request::Parts;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
 use serde_json::Value;
 use std::collections::HashMap;
-use chrono::Utc;
-use crate::config::AppState;
-use crate::repositories::IssRepository;
+use chrono::{DateTime, Duration, Utc};
+use crate::config::{parse_duration, AppState};
+use crate::errors::{ApiError, ApiOk, ApiResult};
+use crate::scheduler::{run_locked, RunOutcome};
 use crate::services::IssService;
-use crate::domain::{Trend, Health};
+use crate::domain::{Trend, TrendPoint, Health, IssTrack, RefreshStatus, Resolution, SpaceCacheItem, SpaceSource, TrackPoint};
+use crate::geo::{export, haversine_km, split_antimeridian, trend};
+use crate::orbit::{self, passes::Observer, Propagator, ISS_NORAD_ID};
+
+pub async fn health_check(State(st): State<AppState>) -> ApiOk<Health> {
+    let upstreams = st.clients.health();
+    let status = if upstreams.iter().any(|u| u.circuit != "closed") { "degraded" } else { "ok" };
+    ApiOk(Health { status, now: Utc::now(), upstreams })
+}
 
-pub async fn health_check() -> Json<Health> {
-    Json(Health { status: "ok", now: Utc::now() })
+pub async fn not_found() -> ApiError {
+    ApiError::NotFound("route not found".into())
 }
 
-pub async fn last_iss(State(st): State<AppState>) -> Result<Json<Value>, (StatusCode, String)> {
-    let row_opt = IssRepository::get_last_iss(&st.pool).await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+pub async fn last_iss(State(st): State<AppState>) -> ApiResult<Value> {
+    let row_opt = st.repos.iss.last().await?;
 
-    if let Some((id, fetched_at, source_url, payload)) = row_opt {
-        return Ok(Json(serde_json::json!({
-            "id": id, "fetched_at": fetched_at, "source_url": source_url, "payload": payload
+    if let Some(r) = row_opt {
+        return Ok(ApiOk(serde_json::json!({
+            "id": r.id, "fetched_at": r.fetched_at, "source_url": r.source_url, "payload": r.payload,
+            "position": r.position,
         })));
     }
-    Ok(Json(serde_json::json!({"message":"no data"})))
+    Ok(ApiOk(serde_json::json!({"message":"no data"})))
 }
 
-pub async fn trigger_iss(State(st): State<AppState>) -> Result<Json<Value>, (StatusCode, String)> {
-    IssService::fetch_and_store_iss(&st.pool, &st.fallback_url).await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+pub async fn trigger_iss(State(st): State<AppState>) -> ApiResult<Value> {
+    if let RunOutcome::Skipped = run_locked(&*st.repos.locks, "iss", IssService::fetch_and_store_iss(&st)).await? {
+        return Err(ApiError::JobRunning("iss".into()));
+    }
     last_iss(State(st)).await
 }
 
-pub async fn iss_trend(State(st): State<AppState>) -> Result<Json<Trend>, (StatusCode, String)> {
-    let rows = IssRepository::get_iss_trend_data(&st.pool).await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+/// `GET /iss/trend?window=&limit=&resolution=` — сдвиг между двумя последними измерениями и статистика за окно
+/// (по умолчанию один виток); `limit` добавляет последние точки окна для графиков
+pub async fn iss_trend(Query(q): Query<HashMap<String,String>>, State(st): State<AppState>) -> ApiResult<Trend> {
+    let window = match q.get("window") {
+        Some(s) => parse_duration(s, 1000)
+            .and_then(|d| Duration::from_std(d).ok())
+            .filter(|d| *d > Duration::zero() && *d <= Duration::days(DAILY_MAX_DAYS))
+            .ok_or_else(|| ApiError::validation(format!("window must be a duration like 90m or 6h, up to {DAILY_MAX_DAYS} days")))?,
+        None => Duration::minutes(TRACK_DEFAULT_MINUTES),
+    };
+    let limit = q.get("limit").map(|s| s.parse::<usize>().ok().filter(|n| (1..=TRACK_MAX_POINTS).contains(n))
+        .ok_or_else(|| ApiError::validation(format!("limit must be an integer in 1..={TRACK_MAX_POINTS}"))))
+        .transpose()?;
 
+    let to = Utc::now();
+    let from = to - window;
+    let (stats, points) = match resolution(&q, from, to, TREND_RAW_MAX_DAYS, &st)? {
+        Resolution::Raw => {
+            let positions = st.repos.iss.window(from, to).await?;
+            let points = limit.map(|n| positions[positions.len().saturating_sub(n)..].iter().map(TrendPoint::from).collect());
+            (trend::analyze(&positions, from, to, st.settings.interval("iss")), points)
+        }
+        res => {
+            let rollups = st.repos.iss.rollups(res, from, to).await?;
+            let points = limit.map(|n| rollups[rollups.len().saturating_sub(n)..].iter().map(TrendPoint::from).collect());
+            (trend::analyze_rollups(&rollups, res, from, to), points)
+        }
+    };
+
+    let rows = st.repos.iss.trend_data().await?;
     if rows.len() < 2 {
-        return Ok(Json(Trend {
+        return Ok(ApiOk(Trend {
             movement: false, delta_km: 0.0, dt_sec: 0.0, velocity_kmh: None,
             from_time: None, to_time: None,
-            from_lat: None, from_lon: None, to_lat: None, to_lon: None
+            from_lat: None, from_lon: None, to_lat: None, to_lon: None,
+            window: stats, points,
         }));
     }
 
     let (t2, p2) = &rows[0];
     let (t1, p1) = &rows[1];
 
-    let lat1 = num(&p1["latitude"]);
-    let lon1 = num(&p1["longitude"]);
-    let lat2 = num(&p2["latitude"]);
-    let lon2 = num(&p2["longitude"]);
-    let v2 = num(&p2["velocity"]);
-
-    let mut delta_km = 0.0;
-    let mut movement = false;
-    if let (Some(a1), Some(o1), Some(a2), Some(o2)) = (lat1, lon1, lat2, lon2) {
-        delta_km = haversine_km(a1, o1, a2, o2);
-        movement = delta_km > 0.1;
-    }
+    let delta_km = haversine_km(p1.lat, p1.lon, p2.lat, p2.lon);
     let dt_sec = (*t2 - *t1).num_milliseconds() as f64 / 1000.0;
+    // скорость — по времени измерения: у застывшего апстрима оно не меняется вместе с координатами
+    let moving_sec = (p2.timestamp - p1.timestamp).num_milliseconds() as f64 / 1000.0;
 
-    Ok(Json(Trend {
-        movement,
+    Ok(ApiOk(Trend {
+        movement: moving_sec > 0.0 && delta_km / moving_sec * 3600.0 >= TREND_MOVING_MIN_KMH,
         delta_km,
         dt_sec,
-        velocity_kmh: v2,
+        velocity_kmh: p2.velocity_kmh,
         from_time: Some(*t1),
         to_time: Some(*t2),
-        from_lat: lat1, from_lon: lon1, to_lat: lat2, to_lon: lon2,
+        from_lat: Some(p1.lat), from_lon: Some(p1.lon), to_lat: Some(p2.lat), to_lon: Some(p2.lon),
+        window: stats,
+        points,
     }))
 }
 
-pub async fn osdr_sync(State(st): State<AppState>) -> Result<Json<Value>, (StatusCode, String)> {
-    let written = IssService::fetch_and_store_osdr(&st).await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    Ok(Json(serde_json::json!({ "written": written })))
+/// Подспутниковая точка МКС идёт ~25 000 км/ч; заметно медленнее — апстрим отдаёт застывшие координаты
+const TREND_MOVING_MIN_KMH: f64 = 1000.0;
+const TREND_RAW_MAX_DAYS: i64 = 7;
+
+/// Детализация по `?resolution=auto|raw|hour|day`. В режиме `auto` берутся сырые строки, пока интервал
+/// не длиннее `raw_max_days` и ещё не удалён по сроку хранения, дальше — почасовые, затем суточные агрегаты.
+fn resolution(q: &HashMap<String,String>, from: DateTime<Utc>, to: DateTime<Utc>, raw_max_days: i64, st: &AppState) -> Result<Resolution, ApiError> {
+    let span = to - from;
+    let raw_kept = Duration::from_std(st.settings.iss_raw_retention).is_ok_and(|keep| from >= Utc::now() - keep);
+    let res = match q.get("resolution").map(String::as_str) {
+        None | Some("auto") if raw_kept && span <= Duration::days(raw_max_days) => Resolution::Raw,
+        None | Some("auto") if span <= Duration::days(HOURLY_MAX_DAYS) => Resolution::Hour,
+        None | Some("auto") => Resolution::Day,
+        Some(s) => Resolution::parse(s).ok_or_else(|| ApiError::validation("resolution must be one of auto, raw, hour, day"))?,
+    };
+    let max_days = match res {
+        Resolution::Raw => raw_max_days,
+        Resolution::Hour => HOURLY_MAX_DAYS,
+        Resolution::Day => DAILY_MAX_DAYS,
+    };
+    if span > Duration::days(max_days) {
+        return Err(ApiError::validation(format!("range must not exceed {max_days} days at resolution {}", res.as_str())));
+    }
+    Ok(res)
 }
 
-pub async fn osdr_list(State(st): State<AppState>) -> Result<Json<Value>, (StatusCode, String)> {
-    let limit = std::env::var("OSDR_LIST_LIMIT").ok()
-        .and_then(|s| s.parse::<i64>().ok()).unwrap_or(20);
+const HOURLY_MAX_DAYS: i64 = 366;
+const DAILY_MAX_DAYS: i64 = 3660;
 
-    let items = IssRepository::get_osdr_list(&st.pool, limit).await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+/// `GET /iss/track?from=&to=&max_points=&resolution=` — наземный трек за интервал (по умолчанию последний виток)
+pub async fn iss_track(Query(q): Query<HashMap<String,String>>, State(st): State<AppState>) -> ApiResult<IssTrack> {
+    let (track, _) = load_track(&q, &st).await?;
+    Ok(ApiOk(track))
+}
 
-    Ok(Json(serde_json::json!({ "items": items })))
+/// `GET /iss/track.geojson` — тот же трек как FeatureCollection (линия + точки измерений)
+pub async fn iss_track_geojson(Query(q): Query<HashMap<String,String>>, State(st): State<AppState>) -> Result<Response, ApiError> {
+    let (track, samples) = load_track(&q, &st).await?;
+    let body = serde_json::to_string(&export::geojson(&track, &samples)).map_err(anyhow::Error::from)?;
+    Ok(download("application/geo+json", &track, "geojson", body))
 }
 
-pub async fn space_latest(Path(src): Path<String>, State(st): State<AppState>) -> Result<Json<Value>, (StatusCode, String)> {
-    let item_opt = IssRepository::get_latest_space_cache(&st.pool, &src).await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+/// `GET /iss/track.kml` — для Google Earth
+pub async fn iss_track_kml(Query(q): Query<HashMap<String,String>>, State(st): State<AppState>) -> Result<Response, ApiError> {
+    let (track, samples) = load_track(&q, &st).await?;
+    Ok(download("application/vnd.google-earth.kml+xml", &track, "kml", export::kml(&track, &samples)))
+}
+
+fn download(content_type: &'static str, track: &IssTrack, ext: &str, body: String) -> Response {
+    let file = format!("attachment; filename=\"iss-track-{}.{ext}\"", track.from.format("%Y%m%dT%H%M%SZ"));
+    ([(header::CONTENT_TYPE, content_type.to_string()), (header::CONTENT_DISPOSITION, file)], body).into_response()
+}
+
+/// Разбор `from`/`to`/`max_points`/`resolution` и выборка трека; вторым — исходные точки без интерполяции на антимеридиане
+async fn load_track(q: &HashMap<String,String>, st: &AppState) -> Result<(IssTrack, Vec<TrackPoint>), ApiError> {
+    let to = time_param(q, "to")?.unwrap_or_else(Utc::now);
+    let from = time_param(q, "from")?.unwrap_or(to - Duration::minutes(TRACK_DEFAULT_MINUTES));
+    if from >= to {
+        return Err(ApiError::validation("from must be earlier than to"));
+    }
+    let res = resolution(q, from, to, TRACK_MAX_DAYS, st)?;
+    let max_points = match q.get("max_points") {
+        Some(s) => s.parse::<usize>().ok().filter(|n| (2..=TRACK_MAX_POINTS).contains(n))
+            .ok_or_else(|| ApiError::validation(format!("max_points must be an integer in 2..={TRACK_MAX_POINTS}")))?,
+        None => TRACK_DEFAULT_POINTS,
+    };
+
+    let samples: Vec<TrackPoint> = match res {
+        Resolution::Raw => st.repos.iss.track(from, to, max_points).await?.iter().map(TrackPoint::from).collect(),
+        _ => {
+            let rollups = st.repos.iss.rollups(res, from, to).await?;
+            let step = rollups.len().div_ceil(max_points).max(1);
+            rollups.iter().step_by(step).map(TrackPoint::from).collect()
+        }
+    };
+    let segments = split_antimeridian(samples.clone());
+    Ok((IssTrack { from, to, resolution: res, max_points, points: samples.len(), segments }, samples))
+}
+
+fn time_param(q: &HashMap<String,String>, k: &str) -> Result<Option<DateTime<Utc>>, ApiError> {
+    q.get(k).map(|s| s.parse::<DateTime<Utc>>()
+        .map_err(|_| ApiError::validation(format!("{k} must be an RFC 3339 timestamp"))))
+        .transpose()
+}
+
+/// Один виток МКС ≈ 92 минуты
+const TRACK_DEFAULT_MINUTES: i64 = 92;
+const TRACK_DEFAULT_POINTS: usize = 500;
+const TRACK_MAX_POINTS: usize = 5000;
+const TRACK_MAX_DAYS: i64 = 31;
+
+/// `GET /iss/predict?at=` — позиция по SGP4 из последнего TLE (по умолчанию — сейчас)
+pub async fn iss_predict(Query(q): Query<HashMap<String,String>>, State(st): State<AppState>) -> ApiResult<Value> {
+    let at = match q.get("at") {
+        Some(s) => s.parse::<DateTime<Utc>>().map_err(|_| ApiError::validation("at must be an RFC 3339 timestamp"))?,
+        None => Utc::now(),
+    };
+    let tle = st.repos.tle.latest(ISS_NORAD_ID).await?
+        .ok_or_else(|| ApiError::NotFound("no TLE loaded yet".into()))?;
+    orbit::check_tle_age(&tle, at, st.settings.tle_max_age).map_err(ApiError::validation)?;
+    let position = Propagator::new(tle.clone())
+        .and_then(|p| p.position_at(at))
+        .map_err(anyhow::Error::from)?;
+
+    Ok(ApiOk(serde_json::json!({
+        "at": at,
+        "position": position,
+        "tle": {
+            "name": tle.name,
+            "epoch": tle.epoch,
+            "age_hours": (at - tle.epoch).num_seconds() as f64 / 3600.0,
+            "line1": tle.line1,
+            "line2": tle.line2,
+        },
+    })))
+}
+
+/// `GET /iss/passes?lat=&lon=&alt=&days=` — пролёты над точкой в окне `[now, now + days)`.
+/// `alt` — метры над эллипсоидом. Уже закончившиеся пролёты не отдаются, идущий сейчас — отдаётся.
+/// Окно целиком должно укладываться в `TLE_MAX_AGE_SECONDS` от эпохи TLE, поэтому допустимое `days` бывает и меньше 10.
+pub async fn iss_passes(Query(q): Query<HashMap<String,String>>, State(st): State<AppState>) -> ApiResult<Value> {
+    passes_at(&st, &q, Utc::now()).await
+}
+
+async fn passes_at(st: &AppState, q: &HashMap<String,String>, now: DateTime<Utc>) -> ApiResult<Value> {
+    let num = |k: &str, range: std::ops::RangeInclusive<f64>, default: Option<f64>| -> Result<f64, ApiError> {
+        match q.get(k) {
+            Some(s) => s.parse::<f64>().ok().filter(|v| range.contains(v))
+                .ok_or_else(|| ApiError::validation(format!("{k} must be a number in {}..={}", range.start(), range.end()))),
+            None => default.ok_or_else(|| ApiError::validation(format!("{k} is required"))),
+        }
+    };
+    let obs = Observer::rounded(num("lat", -90.0..=90.0, None)?, num("lon", -180.0..=180.0, None)?, num("alt", -500.0..=9000.0, Some(0.0))?);
+    let days = match q.get("days") {
+        Some(s) => s.parse::<u32>().ok().filter(|d| (1..=PASSES_MAX_DAYS).contains(d))
+            .ok_or_else(|| ApiError::validation(format!("days must be an integer in 1..={PASSES_MAX_DAYS}")))?,
+        None => 1,
+    };
+
+    let tle = st.repos.tle.latest(ISS_NORAD_ID).await?
+        .ok_or_else(|| ApiError::NotFound("no TLE loaded yet".into()))?;
+    let end = now + Duration::days(days as i64);
+    let valid_until = orbit::tle_valid_until(&tle, st.settings.tle_max_age);
+    if let Err(e) = orbit::check_tle_age(&tle, end, st.settings.tle_max_age) {
+        let covered = (valid_until - now).num_days().min(PASSES_MAX_DAYS as i64);
+        return Err(ApiError::validation(match covered {
+            1.. => format!("days must be at most {covered} with the current TLE: {e}"),
+            _ => e,
+        }));
+    }
+    // кэш посуточный: от полуночи считаем столько суток, чтобы накрыть всё окно
+    let day = now.date_naive();
+    let midnight = day.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    let span = days + u32::from(now > midnight);
+    let tle_epoch = tle.epoch;
+    let prop = Propagator::new(tle).map_err(anyhow::Error::from)?;
+    let cache = st.passes.clone();
+    // несколько тысяч прогонов SGP4 на сутки — не держим ими рантайм
+    let passes = tokio::task::spawn_blocking(move || cache.get_or_compute(obs, day, span, valid_until, &prop))
+        .await.map_err(anyhow::Error::from)?
+        .map_err(anyhow::Error::from)?;
+    let window: Vec<_> = passes.iter().filter(|p| p.set.at > now && p.rise.at < end).collect();
+
+    Ok(ApiOk(serde_json::json!({
+        "observer": { "lat": obs.lat, "lon": obs.lon, "alt_m": obs.alt_m },
+        "from": now,
+        "to": end,
+        "tle_epoch": tle_epoch,
+        "passes": window,
+    })))
+}
+
+const PASSES_MAX_DAYS: u32 = 10;
+
+pub async fn osdr_sync(State(st): State<AppState>) -> ApiResult<Value> {
+    match run_locked(&*st.repos.locks, "osdr", IssService::fetch_and_store_osdr(&st)).await? {
+        RunOutcome::Ran(written) => Ok(ApiOk(serde_json::json!({ "written": written }))),
+        RunOutcome::Skipped => Err(ApiError::JobRunning("osdr".into())),
+    }
+}
+
+pub async fn osdr_list(Query(q): Query<HashMap<String,String>>, State(st): State<AppState>) -> ApiResult<Value> {
+    let limit = match q.get("limit") {
+        Some(s) => s.parse::<i64>().ok().filter(|n| (1..=1000).contains(n))
+            .ok_or_else(|| ApiError::validation("limit must be an integer in 1..=1000"))?,
+        None => st.settings.osdr_list_limit,
+    };
+
+    let items = st.repos.osdr.list(limit).await?;
+
+    Ok(ApiOk(serde_json::json!({ "items": items })))
+}
+
+/// `:src` из пути: неизвестный источник отсекается с `UNKNOWN_SOURCE` ещё до запроса в БД
+pub struct Source(pub SpaceSource);
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Source {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, ApiError> {
+        let Path(src) = Path::<String>::from_request_parts(parts, state).await
+            .map_err(|e| ApiError::validation(e.body_text()))?;
+        SpaceSource::parse(&src).map(Source).ok_or(ApiError::UnknownSource(src))
+    }
+}
+
+pub async fn space_latest(Source(src): Source, State(st): State<AppState>) -> ApiResult<Value> {
+    let item_opt = st.repos.cache.latest(src.as_str()).await?;
 
     if let Some(item) = item_opt {
-        return Ok(Json(serde_json::json!({ "source": item.source, "fetched_at": item.fetched_at, "payload": item.payload })));
-    }
-    Ok(Json(serde_json::json!({ "source": src, "message":"no data" })))
-}
-
-pub async fn space_refresh(Query(q): Query<HashMap<String,String>>, State(st): State<AppState>) -> Result<Json<Value>, (StatusCode, String)> {
-    let list = q.get("src").cloned().unwrap_or_else(|| "apod,neo,flr,cme,spacex".to_string());
-    let mut done = Vec::new();
-    for s in list.split(',').map(|x| x.trim().to_lowercase()) {
-        match s.as_str() {
-            "apod"   => { let _ = IssService::fetch_apod(&st).await;       done.push("apod"); }
-            "neo"    => { let _ = IssService::fetch_neo_feed(&st).await;   done.push("neo"); }
-            "flr"    => { let _ = IssService::fetch_donki(&st).await;      done.push("flr"); }
-            "cme"    => { let _ = IssService::fetch_donki(&st).await;      done.push("cme"); }
-            "spacex" => { let _ = IssService::fetch_spacex_next(&st).await; done.push("spacex"); }
-            _ => {}
+        return Ok(ApiOk(serde_json::json!({ "source": item.source, "fetched_at": item.fetched_at, "last_seen_at": item.last_seen_at, "payload": item.payload })));
+    }
+    Ok(ApiOk(serde_json::json!({ "source": src, "message":"no data" })))
+}
+
+/// `GET /space/:src/history?from=&to=&limit=` — сохранённые версии снимка (без содержимого), новые первыми
+pub async fn space_history(Source(src): Source, Query(q): Query<HashMap<String,String>>, State(st): State<AppState>) -> ApiResult<Value> {
+    let (from, to) = (time_param(&q, "from")?, time_param(&q, "to")?);
+    if let (Some(f), Some(t)) = (from, to) {
+        if f > t {
+            return Err(ApiError::validation("from must not be later than to"));
         }
     }
-    Ok(Json(serde_json::json!({ "refreshed": done })))
+    let limit = match q.get("limit") {
+        Some(s) => s.parse::<i64>().ok().filter(|n| (1..=HISTORY_MAX_LIMIT).contains(n))
+            .ok_or_else(|| ApiError::validation(format!("limit must be an integer in 1..={HISTORY_MAX_LIMIT}")))?,
+        None => HISTORY_DEFAULT_LIMIT,
+    };
+    let versions = st.repos.cache.history(src.as_str(), from, to, limit).await?;
+    Ok(ApiOk(serde_json::json!({ "source": src, "count": versions.len(), "versions": versions })))
 }
 
-pub async fn space_summary(State(st): State<AppState>) -> Result<Json<Value>, (StatusCode, String)> {
-    let apod   = IssRepository::get_latest_space_cache(&st.pool, "apod").await.unwrap_or(None);
-    let neo    = IssRepository::get_latest_space_cache(&st.pool, "neo").await.unwrap_or(None);
-    let flr    = IssRepository::get_latest_space_cache(&st.pool, "flr").await.unwrap_or(None);
-    let cme    = IssRepository::get_latest_space_cache(&st.pool, "cme").await.unwrap_or(None);
-    let spacex = IssRepository::get_latest_space_cache(&st.pool, "spacex").await.unwrap_or(None);
+const HISTORY_DEFAULT_LIMIT: i64 = 50;
+const HISTORY_MAX_LIMIT: i64 = 500;
 
-    let iss_last = IssRepository::get_last_iss(&st.pool).await.unwrap_or(None);
-    let osdr_count = IssRepository::get_osdr_count(&st.pool).await.unwrap_or(0);
+/// `GET /space/:src/diff?a=&b=` — RFC 6902 JSON Patch, превращающий версию `a` в `b`.
+/// По умолчанию `b` — последняя версия, `a` — предыдущая перед `b`.
+pub async fn space_diff(Source(src): Source, Query(q): Query<HashMap<String,String>>, State(st): State<AppState>) -> ApiResult<Value> {
+    let id = |k: &str| -> Result<Option<i64>, ApiError> {
+        q.get(k).map(|s| s.parse::<i64>().map_err(|_| ApiError::validation(format!("{k} must be a version id"))))
+            .transpose()
+    };
+    let (a, b) = (id("a")?, id("b")?);
+    let missing = |what: String| ApiError::NotFound(format!("{what} not found for source {}", src.as_str()));
 
-    Ok(Json(serde_json::json!({
-        "apod": apod.map(|x| serde_json::json!({"at": x.fetched_at, "payload": x.payload})).unwrap_or(serde_json::json!({})),
-        "neo": neo.map(|x| serde_json::json!({"at": x.fetched_at, "payload": x.payload})).unwrap_or(serde_json::json!({})),
-        "flr": flr.map(|x| serde_json::json!({"at": x.fetched_at, "payload": x.payload})).unwrap_or(serde_json::json!({})),
-        "cme": cme.map(|x| serde_json::json!({"at": x.fetched_at, "payload": x.payload})).unwrap_or(serde_json::json!({})),
-        "spacex": spacex.map(|x| serde_json::json!({"at": x.fetched_at, "payload": x.payload})).unwrap_or(serde_json::json!({})),
-        "iss": iss_last.map(|(_, at, _, p)| serde_json::json!({"at": at, "payload": p})).unwrap_or(serde_json::json!({})),
-        "osdr_count": osdr_count
+    let new = match b {
+        Some(b) => st.repos.cache.version(src.as_str(), b).await?.ok_or_else(|| missing(format!("version {b}")))?,
+        None => st.repos.cache.latest(src.as_str()).await?.ok_or_else(|| ApiError::NotFound(format!("no versions stored for source {}", src.as_str())))?,
+    };
+    let old = match a {
+        Some(a) => st.repos.cache.version(src.as_str(), a).await?.ok_or_else(|| missing(format!("version {a}")))?,
+        None => st.repos.cache.previous(src.as_str(), new.id).await?.ok_or_else(|| missing(format!("version before {}", new.id)))?,
+    };
+
+    let patch = json_patch::diff(&old.payload, &new.payload);
+    let meta = |i: &SpaceCacheItem| serde_json::json!({ "id": i.id, "fetched_at": i.fetched_at, "last_seen_at": i.last_seen_at });
+    Ok(ApiOk(serde_json::json!({
+        "source": src,
+        "a": meta(&old),
+        "b": meta(&new),
+        "operations": patch.0.len(),
+        "patch": patch,
     })))
 }
 
-fn num(v: &Value) -> Option<f64> {
-    if let Some(x) = v.as_f64() { return Some(x); }
-    if let Some(s) = v.as_str() { return s.parse::<f64>().ok(); }
-    None
+/// `GET /space/refresh?src=apod,flr&wait=` — источники (по умолчанию все) обновляются параллельно, в ответе — итог по каждому.
+/// Источник, который уже обновляется, не запрашивается повторно: ответ берётся из идущего прогона.
+/// `wait=false` — сразу 202 с `id`, состояние — `GET /space/refresh/:id`; так же, если не уложились в [`REFRESH_WAIT`].
+pub async fn space_refresh(Query(q): Query<HashMap<String,String>>, State(st): State<AppState>) -> Result<Response, ApiError> {
+    let mut sources = Vec::new();
+    match q.get("src") {
+        None => sources.extend(SpaceSource::ALL),
+        Some(list) => for s in list.split(',').map(|x| x.trim().to_lowercase()).filter(|x| !x.is_empty()) {
+            let src = SpaceSource::parse(&s).ok_or(ApiError::UnknownSource(s))?;
+            if !sources.contains(&src) {
+                sources.push(src);
+            }
+        },
+    }
+    let wait = match q.get("wait").map(String::as_str) {
+        None | Some("true") | Some("1") => true,
+        Some("false") | Some("0") => false,
+        Some(_) => return Err(ApiError::validation("wait must be true or false")),
+    };
+
+    let run = st.refresh.start(&st, &sources);
+    if !wait {
+        return Ok((StatusCode::ACCEPTED, ApiOk(run.status())).into_response());
+    }
+    Ok(ApiOk(run.wait(REFRESH_WAIT).await).into_response())
+}
+
+/// Дольше синхронный ответ не держим — клиенты и прокси обычно рвут запрос около минуты; остальное — через `/space/refresh/:id`
+const REFRESH_WAIT: std::time::Duration = std::time::Duration::from_secs(45);
+
+/// `GET /space/refresh/:id` — состояние прогона `/space/refresh` (помнятся последние 64)
+pub async fn space_refresh_status(Path(id): Path<String>, State(st): State<AppState>) -> ApiResult<RefreshStatus> {
+    let run = st.refresh.get(&id).ok_or_else(|| ApiError::NotFound(format!("refresh {id} not found")))?;
+    Ok(ApiOk(run.status()))
+}
+
+/// `GET /space/stats` — версии и объём `space_cache` по источникам, счётчики дедупликации и очистки, политика хранения
+pub async fn space_stats(State(st): State<AppState>) -> ApiResult<Value> {
+    let sources = st.repos.cache.stats().await?;
+    let retention: serde_json::Map<String, Value> = st.settings.space_retention.iter()
+        .map(|(src, r)| (src.as_str().to_string(), serde_json::json!({ "versions": r.versions, "max_age_seconds": r.max_age.as_secs() })))
+        .collect();
+    Ok(ApiOk(serde_json::json!({ "sources": sources, "retention": retention })))
+}
+
+pub async fn space_summary(State(st): State<AppState>) -> ApiResult<Value> {
+    let mut out = serde_json::Map::new();
+    for src in SpaceSource::ALL {
+        let item = st.repos.cache.latest(src.as_str()).await?;
+        out.insert(src.as_str().into(), item.map(|x| serde_json::json!({"at": x.fetched_at, "payload": x.payload})).unwrap_or(serde_json::json!({})));
+    }
+
+    let iss_last = st.repos.iss.last().await?;
+    let osdr_count = st.repos.osdr.count().await?;
+    out.insert("iss".into(), iss_last.map(|r| serde_json::json!({"at": r.fetched_at, "payload": r.payload})).unwrap_or(serde_json::json!({})));
+    out.insert("osdr_count".into(), osdr_count.into());
+    Ok(ApiOk(Value::Object(out)))
+}
+
+/// `GET /space/sources` — каталог источников: что это, откуда, как часто обновляется и чем кончилось последнее обновление
+pub async fn space_sources(State(st): State<AppState>) -> ApiResult<Value> {
+    let jobs = st.jobs.status(&*st.repos.jobs).await?;
+    let mut out = Vec::new();
+    for src in SpaceSource::ALL {
+        let job = jobs.iter().find(|j| j.name == src.job());
+        let latest = st.repos.cache.latest(src.as_str()).await?;
+        let status = match job {
+            Some(j) if j.running => "running",
+            Some(j) if j.last_finished.is_none() => "never",
+            Some(j) if j.last_error.is_some() => "error",
+            Some(_) => "ok",
+            None => "unknown",
+        };
+        let upstream = st.clients.space_health(src);
+        out.push(serde_json::json!({
+            "source": src,
+            "description": src.description(),
+            "upstream_url": st.clients.space_url(src),
+            "upstream_circuit": upstream.circuit,
+            "job": src.job(),
+            "interval_seconds": st.settings.interval(src.job()).as_secs(),
+            "last_fetch": {
+                "status": status,
+                "started_at": job.and_then(|j| j.last_started),
+                "finished_at": job.and_then(|j| j.last_finished),
+                "error": job.and_then(|j| j.last_error.clone()),
+            },
+            "latest": latest.map(|i| serde_json::json!({ "id": i.id, "fetched_at": i.fetched_at, "last_seen_at": i.last_seen_at })),
+        }));
+    }
+    Ok(ApiOk(serde_json::json!({ "sources": out })))
 }
 
-fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
-    let rlat1 = lat1.to_radians();
-    let rlat2 = lat2.to_radians();
-    let dlat = (lat2 - lat1).to_radians();
-    let dlon = (lon2 - lon1).to_radians();
-    let a = (dlat / 2.0).sin().powi(2) + rlat1.cos() * rlat2.cos() * (dlon / 2.0).sin().powi(2);
-    let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
-    6371.0 * c
-}
\ No newline at end of file
+pub async fn jobs_list(State(st): State<AppState>) -> ApiResult<Value> {
+    let jobs = st.jobs.status(&*st.repos.jobs).await?;
+    Ok(ApiOk(serde_json::json!({ "jobs": jobs })))
+}
+
+pub async fn job_run(Path(name): Path<String>, State(st): State<AppState>) -> ApiResult<Value> {
+    let job = st.jobs.get(&name).ok_or_else(|| ApiError::NotFound(format!("unknown job {name}")))?;
+    if let RunOutcome::Skipped = job.run(&st).await? {
+        return Err(ApiError::JobRunning(name));
+    }
+    job_status(&st, &name).await
+}
+
+pub async fn job_pause(Path(name): Path<String>, State(st): State<AppState>) -> ApiResult<Value> {
+    let job = st.jobs.get(&name).ok_or_else(|| ApiError::NotFound(format!("unknown job {name}")))?;
+    job.set_paused(&*st.repos.jobs, true).await?;
+    job_status(&st, &name).await
+}
+
+pub async fn job_resume(Path(name): Path<String>, State(st): State<AppState>) -> ApiResult<Value> {
+    let job = st.jobs.get(&name).ok_or_else(|| ApiError::NotFound(format!("unknown job {name}")))?;
+    job.set_paused(&*st.repos.jobs, false).await?;
+    job_status(&st, &name).await
+}
+
+async fn job_status(st: &AppState, name: &str) -> ApiResult<Value> {
+    let jobs = st.jobs.status(&*st.repos.jobs).await?;
+    let job = jobs.into_iter().find(|j| j.name == name);
+    Ok(ApiOk(serde_json::json!({ "job": job })))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::config::AppState;
+    use std::collections::HashMap;
+    use chrono::{DateTime, Duration, NaiveTime, Utc};
+    use crate::domain::IssPosition;
+    use crate::orbit::tle::Tle;
+    use crate::routes::app_router;
+    use crate::testing::{self, call, ISS_TLE};
+    use super::passes_at;
+
+    async fn app(vars: &[(&str, &str)]) -> (AppState, String) {
+        let st = AppState::in_memory(vars).await;
+        let url = testing::serve(app_router(st.clone())).await;
+        (st, url)
+    }
+
+    #[tokio::test]
+    async fn summary_on_empty_store() {
+        let (_, url) = app(&[]).await;
+        let (status, body) = call(Method::GET, &format!("{url}/space/summary")).await;
+        assert_eq!((status, &body["ok"]), (200, &json!(true)));
+        assert_eq!(body["data"]["osdr_count"], 0);
+        assert_eq!(body["data"]["apod"], json!({}));
+        assert_eq!(body["data"]["iss"], json!({}));
+    }
+
+    #[tokio::test]
+    async fn last_returns_logged_position() {
+        let (st, url) = app(&[]).await;
+        let payload = json!({ "latitude": 1.5, "longitude": 2.5 });
+        let pos = IssPosition::from_wheretheiss(&payload, chrono::Utc::now()).unwrap();
+        st.repos.iss.log_fetch("http://upstream", &pos, payload.clone()).await.unwrap();
+        let (status, body) = call(Method::GET, &format!("{url}/last")).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["data"]["payload"], payload);
+    }
+
+    #[tokio::test]
+    async fn trend_keeps_legacy_fields() {
+        let (st, url) = app(&[]).await;
+        let (status, body) = call(Method::GET, &format!("{url}/iss/trend")).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["data"]["movement"], false);
+        assert_eq!(body["data"]["from_time"], json!(null));
+        assert_eq!(body["data"]["window"]["samples"], 0);
+
+        let now = Utc::now().timestamp();
+        for (dt, lon) in [(60, 10.0), (0, 13.8)] {
+            let payload = json!({ "latitude": 0.0, "longitude": lon, "altitude": 420.0, "velocity": 27600.0, "timestamp": now - dt });
+            let pos = IssPosition::from_wheretheiss(&payload, Utc::now()).unwrap();
+            st.repos.iss.log_fetch("http://upstream", &pos, payload).await.unwrap();
+        }
+        let (_, body) = call(Method::GET, &format!("{url}/iss/trend")).await;
+        let data = body["data"].as_object().unwrap();
+        for key in ["movement", "delta_km", "dt_sec", "velocity_kmh", "from_time", "to_time", "from_lat", "from_lon", "to_lat", "to_lon", "window"] {
+            assert!(data.contains_key(key), "missing {key}");
+        }
+        assert!(!data.contains_key("points"));
+        assert_eq!(data["movement"], true);
+        assert_eq!((data["from_lon"].as_f64(), data["to_lon"].as_f64()), (Some(10.0), Some(13.8)));
+        assert_eq!(data["velocity_kmh"], 27600.0);
+        assert_eq!(data["window"]["samples"], 2);
+
+        let (_, body) = call(Method::GET, &format!("{url}/iss/trend?window=2h&limit=1")).await;
+        assert_eq!(body["data"]["points"].as_array().map(Vec::len), Some(1));
+        assert_eq!(body["data"]["window"]["seconds"], 7200);
+        assert_eq!(body["data"]["window"]["resolution"], "raw");
+        // длинное окно — по почасовым агрегатам
+        let (_, body) = call(Method::GET, &format!("{url}/iss/trend?window=8d")).await;
+        assert_eq!(body["data"]["window"]["resolution"], "hour");
+        let (status, _) = call(Method::GET, &format!("{url}/iss/trend?window=soon")).await;
+        assert_eq!(status, 400);
+    }
+
+    #[tokio::test]
+    async fn history_and_diff_between_versions() {
+        let (st, url) = app(&[]).await;
+        for title in ["first", "second"] {
+            st.repos.cache.write("apod", json!({ "title": title, "media_type": "image" })).await.unwrap();
+        }
+        let (_, body) = call(Method::GET, &format!("{url}/space/apod/history")).await;
+        assert_eq!(body["data"]["count"], 2);
+        let (status, body) = call(Method::GET, &format!("{url}/space/apod/diff")).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["data"]["patch"], json!([{ "op": "replace", "path": "/title", "value": "second" }]));
+        let (status, body) = call(Method::GET, &format!("{url}/space/neo/diff")).await;
+        assert_eq!((status, body["error"]["code"].as_str()), (404, Some("NOT_FOUND")));
+    }
+
+    #[tokio::test]
+    async fn unknown_source_is_rejected() {
+        let (_, url) = app(&[]).await;
+        let (status, body) = call(Method::GET, &format!("{url}/space/mars/latest")).await;
+        assert_eq!(status, 400);
+        assert_eq!(body["ok"], false);
+        assert_eq!(body["error"]["code"], "UNKNOWN_SOURCE");
+        let (status, body) = call(Method::GET, &format!("{url}/space/refresh?src=apod,mars")).await;
+        assert_eq!((status, body["error"]["code"].as_str()), (400, Some("UNKNOWN_SOURCE")));
+    }
+
+    #[tokio::test]
+    async fn manual_trigger_conflicts_with_running_job() {
+        let (st, url) = app(&[]).await;
+        let _lock = st.repos.locks.try_lock("iss").await.unwrap().unwrap();
+        let (status, body) = call(Method::GET, &format!("{url}/fetch")).await;
+        assert_eq!((status, body["error"]["code"].as_str()), (409, Some("JOB_RUNNING")));
+    }
+
+    #[tokio::test]
+    async fn pause_and_resume_job() {
+        let (_, url) = app(&[]).await;
+        let (status, body) = call(Method::POST, &format!("{url}/jobs/apod/pause")).await;
+        assert_eq!((status, &body["data"]["job"]["paused"]), (200, &json!(true)));
+        let (_, body) = call(Method::GET, &format!("{url}/jobs")).await;
+        let apod = body["data"]["jobs"].as_array().unwrap().iter().find(|j| j["name"] == "apod").unwrap().clone();
+        assert_eq!(apod["paused"], true);
+
+        let (_, body) = call(Method::POST, &format!("{url}/jobs/apod/resume")).await;
+        assert_eq!(body["data"]["job"]["paused"], false);
+        let (status, body) = call(Method::POST, &format!("{url}/jobs/nope/pause")).await;
+        assert_eq!((status, body["error"]["code"].as_str()), (404, Some("NOT_FOUND")));
+    }
+
+    #[tokio::test]
+    async fn passes_window_limited_by_tle_age() {
+        let tle = Tle::from_lines(None, ISS_TLE[0], ISS_TLE[1]).unwrap();
+        // TLE годна ещё примерно на 2,5 суток от текущего момента
+        let max_age = (Utc::now() - tle.epoch).num_seconds() + 60 * 3600;
+        let (st, url) = app(&[("TLE_MAX_AGE_SECONDS", &max_age.to_string())]).await;
+        let (status, _) = call(Method::GET, &format!("{url}/iss/passes?lat=55.75&lon=37.62")).await;
+        assert_eq!(status, 404);
+
+        st.repos.tle.save(&tle, "test").await.unwrap();
+        let (status, body) = call(Method::GET, &format!("{url}/iss/passes?lat=55.75&lon=37.62&days=10")).await;
+        assert_eq!(status, 400);
+        let msg = body["error"]["message"].as_str().unwrap();
+        assert!(msg.starts_with("days must be at most 2 "), "{msg}");
+
+        let (status, body) = call(Method::GET, &format!("{url}/iss/passes?lat=55.75&lon=37.62&days=2")).await;
+        assert_eq!(status, 200);
+        let at = |v: &serde_json::Value| v.as_str().unwrap().parse::<DateTime<Utc>>().unwrap();
+        let (from, to) = (at(&body["data"]["from"]), at(&body["data"]["to"]));
+        assert_eq!(to - from, Duration::days(2));
+        for p in body["data"]["passes"].as_array().unwrap() {
+            assert!(at(&p["set"]["at"]) > from && at(&p["rise"]["at"]) < to);
+        }
+    }
+
+    #[tokio::test]
+    async fn late_request_covers_next_day() {
+        let tle = Tle::from_lines(None, ISS_TLE[0], ISS_TLE[1]).unwrap();
+        let st = AppState::in_memory(&[("TLE_MAX_AGE_SECONDS", "2000000000")]).await;
+        st.repos.tle.save(&tle, "test").await.unwrap();
+        // за 10 минут до полуночи UTC: окно — ближайшие сутки, а не остаток текущих
+        let day = tle.epoch.date_naive() + Duration::days(1);
+        let now = day.and_time(NaiveTime::from_hms_opt(23, 50, 0).unwrap()).and_utc();
+        let q = HashMap::from([("lat".to_string(), "55.75".to_string()), ("lon".to_string(), "37.62".to_string())]);
+
+        let body = passes_at(&st, &q, now).await.unwrap().0;
+        assert_eq!(body["to"], json!(now + Duration::days(1)));
+        let passes = body["passes"].as_array().unwrap();
+        let rises: Vec<DateTime<Utc>> = passes.iter().map(|p| p["rise"]["at"].as_str().unwrap().parse().unwrap()).collect();
+        assert!(rises.len() >= 3, "{rises:?}");
+        assert!(rises.iter().all(|r| *r < now + Duration::days(1)));
+        assert!(passes.iter().all(|p| p["set"]["at"].as_str().unwrap().parse::<DateTime<Utc>>().unwrap() > now));
+        assert!(rises.iter().any(|r| *r > now + Duration::hours(12)));
+    }
+}
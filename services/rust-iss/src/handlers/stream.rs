@@ -0,0 +1,199 @@
+use std::convert::Infallible;
+use std::future::ready;
+use std::time::Duration;
+use axum::extract::ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::Response;
+use futures_util::{stream, SinkExt, Stream, StreamExt};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tracing::debug;
+use crate::config::AppState;
+use crate::errors::ApiError;
+use crate::live::{Channel, Frame};
+
+/// Пустой кадр раз в 15 с: прокси не закрывают соединение, клиент видит обрыв
+const HEARTBEAT: Duration = Duration::from_secs(15);
+/// Клиент, который столько не принимает кадр, отключается
+const WS_SEND_TIMEOUT: Duration = Duration::from_secs(10);
+/// RFC 6455: 1001 going away, 1008 policy violation
+const WS_GOING_AWAY: u16 = 1001;
+const WS_SLOW_CONSUMER: u16 = 1008;
+
+/// `GET /iss/stream` — SSE: сразу последняя известная позиция, затем каждая новая (`event: position`, `id` — id строки).
+/// Отставший подписчик получает `event: lagged` и отключается — EventSource переподключится сам.
+pub async fn iss_sse(State(st): State<AppState>) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let feed = st.live.subscribe();
+    let initial = latest(&st).await?;
+    debug!(subscribers = st.live.subscribers(), "sse subscriber connected");
+    Ok(sse(&st, feed, initial, Some(Channel::IssPosition)))
+}
+
+/// `GET /events` — все кадры ленты; `event:` — имя канала (`iss_position`, `space_cache`, `osdr_item`, `resync`)
+pub async fn events_sse(State(st): State<AppState>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    debug!(subscribers = st.live.subscribers() + 1, "sse subscriber connected");
+    sse(&st, st.live.subscribe(), None, None)
+}
+
+/// Поток SSE из `feed`; с `only` — только кадры одного канала под именем `position`
+fn sse(
+    st: &AppState,
+    feed: Receiver<Frame>,
+    initial: Option<Frame>,
+    only: Option<Channel>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = stream::iter(initial.map(Ok))
+        .chain(BroadcastStream::new(feed))
+        .filter(move |item| ready(match (item, only) {
+            (Ok(frame), Some(c)) => frame.channel == c,
+            _ => true,
+        }))
+        .scan(false, move |lagged, item| {
+            if *lagged {
+                return ready(None);
+            }
+            let event = match item {
+                Ok(frame) => Event::default()
+                    .event(if only.is_some() { "position" } else { frame.channel.as_str() })
+                    .id(frame.id.to_string())
+                    .data(&*frame.json),
+                Err(BroadcastStreamRecvError::Lagged(n)) => {
+                    *lagged = true;
+                    Event::default().event("lagged").data(serde_json::json!({ "skipped": n }).to_string())
+                }
+            };
+            ready(Some(Ok(event)))
+        })
+        .take_until(st.live.closed());
+
+    Sse::new(events).keep_alive(KeepAlive::new().interval(HEARTBEAT))
+}
+
+/// `GET /ws/iss` — те же позиции текстовыми кадрами JSON; heartbeat — ping
+pub async fn iss_ws(ws: WebSocketUpgrade, State(st): State<AppState>) -> Response {
+    ws.on_upgrade(move |socket| ws_session(socket, st))
+}
+
+async fn ws_session(socket: WebSocket, st: AppState) {
+    let (mut tx, mut rx) = socket.split();
+    let mut feed = st.live.subscribe();
+    debug!(subscribers = st.live.subscribers(), "ws subscriber connected");
+
+    if let Ok(Some(frame)) = latest(&st).await {
+        if !send(&mut tx, Message::Text(frame.json.to_string())).await {
+            return;
+        }
+    }
+
+    let closed = st.live.closed();
+    tokio::pin!(closed);
+    let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + HEARTBEAT, HEARTBEAT);
+    loop {
+        tokio::select! {
+            _ = &mut closed => {
+                close(&mut tx, WS_GOING_AWAY, "server shutdown").await;
+                break;
+            }
+            msg = feed.recv() => match msg {
+                Ok(frame) if frame.channel != Channel::IssPosition => {}
+                Ok(frame) => if !send(&mut tx, Message::Text(frame.json.to_string())).await { break },
+                Err(RecvError::Lagged(n)) => {
+                    debug!(skipped = n, "ws subscriber lagged, dropping");
+                    close(&mut tx, WS_SLOW_CONSUMER, "slow consumer").await;
+                    break;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = heartbeat.tick() => if !send(&mut tx, Message::Ping(Vec::new())).await { break },
+            // входящие кадры не нужны, но читать их надо — иначе не увидим Close и pong
+            incoming = rx.next() => match incoming {
+                None | Some(Err(_)) | Some(Ok(Message::Close(_))) => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    debug!("ws subscriber disconnected");
+}
+
+type WsSink = futures_util::stream::SplitSink<WebSocket, Message>;
+
+async fn send(tx: &mut WsSink, msg: Message) -> bool {
+    matches!(tokio::time::timeout(WS_SEND_TIMEOUT, tx.send(msg)).await, Ok(Ok(())))
+}
+
+async fn close(tx: &mut WsSink, code: u16, reason: &'static str) {
+    send(tx, Message::Close(Some(CloseFrame { code, reason: reason.into() }))).await;
+}
+
+/// Последняя записанная позиция — чтобы новый подписчик не ждал следующего опроса
+async fn latest(st: &AppState) -> anyhow::Result<Option<Frame>> {
+    Ok(st.repos.iss.last().await?.and_then(|r| Some(Frame::position(r.id, r.position.as_ref()?))))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use chrono::Utc;
+    use serde_json::json;
+    use crate::config::AppState;
+    use crate::domain::IssPosition;
+    use crate::live::{Frame, LiveFeed};
+    use crate::routes::app_router;
+    use crate::testing;
+
+    fn position(lat: f64) -> IssPosition {
+        IssPosition::from_wheretheiss(&json!({ "latitude": lat, "longitude": 20.0 }), Utc::now()).unwrap()
+    }
+
+    async fn app(buffer: usize) -> (AppState, String) {
+        let mut st = AppState::in_memory(&[]).await;
+        st.live = Arc::new(LiveFeed::with_buffer(buffer));
+        let url = testing::serve(app_router(st.clone())).await;
+        (st, url)
+    }
+
+    /// Полные события SSE (до пустой строки), пока их не наберётся `n`
+    async fn read_events(resp: &mut reqwest::Response, n: usize) -> Vec<String> {
+        let mut buf = String::new();
+        while buf.matches("\n\n").count() < n {
+            let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), resp.chunk()).await.unwrap().unwrap().unwrap();
+            buf.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+        buf.split("\n\n").filter(|e| !e.is_empty()).map(str::to_string).collect()
+    }
+
+    #[tokio::test]
+    async fn sse_sends_latest_position_first() {
+        let (st, url) = app(4).await;
+        let old = position(1.5);
+        st.repos.iss.log_fetch("http://upstream", &old, json!({})).await.unwrap();
+
+        let mut resp = reqwest::get(format!("{url}/iss/stream")).await.unwrap();
+        assert_eq!(resp.headers()["content-type"], "text/event-stream");
+        st.live.publish(Frame::position(2, &position(-7.25)));
+        let events = read_events(&mut resp, 2).await;
+        assert!(events[0].contains("event: position") && events[0].contains(r#""lat":1.5"#), "{}", events[0]);
+        assert!(events[1].contains("event: position") && events[1].contains(r#""lat":-7.25"#), "{}", events[1]);
+
+        // при остановке сервера поток завершается сам
+        st.live.close();
+        let rest = tokio::time::timeout(std::time::Duration::from_secs(5), resp.text()).await.unwrap().unwrap();
+        assert!(!rest.contains("event: position"));
+    }
+
+    #[tokio::test]
+    async fn lagged_subscriber_is_told_and_dropped() {
+        let (st, url) = app(2).await;
+        let resp = reqwest::get(format!("{url}/iss/stream")).await.unwrap();
+        // тестовый рантайм однопоточный: пока публикуем, сервер не успевает прочитать ни одного кадра
+        for i in 0..5 {
+            st.live.publish(Frame::position(i + 1, &position(i as f64)));
+        }
+        let body = tokio::time::timeout(std::time::Duration::from_secs(5), resp.text()).await.unwrap().unwrap();
+        let events: Vec<&str> = body.split("\n\n").filter(|e| !e.is_empty() && !e.starts_with(':')).collect();
+        assert_eq!(events.len(), 1, "{body}");
+        assert!(events[0].contains("event: lagged"));
+        assert!(events[0].contains(r#"data: {"skipped":3}"#), "{}", events[0]);
+    }
+}
@@ -0,0 +1,159 @@
+use std::time::Duration;
+use serde_json::Value;
+use sqlx::postgres::{PgListener, PgNotification};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+use crate::config::AppState;
+use super::{Channel, Frame};
+
+const BACKOFF_MIN: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+/// Слушает каналы [`Channel::NOTIFY`] до `shutdown`. Держит одно соединение пула.
+/// После обрыва переподключается с растущей паузой, досылает последнюю позицию МКС и кадр `resync`.
+pub async fn run(st: AppState, shutdown: CancellationToken) {
+    let mut backoff = BACKOFF_MIN;
+    let mut last_iss_id = 0;
+    let mut reconnect = false;
+    loop {
+        let mut listener = match connect(&st).await {
+            Ok(l) => l,
+            Err(e) => {
+                warn!("live listener: connect failed, retry in {}s: {e:#}", backoff.as_secs());
+                tokio::select! {
+                    _ = shutdown.cancelled() => return,
+                    _ = tokio::time::sleep(backoff) => {}
+                }
+                backoff = (backoff * 2).min(BACKOFF_MAX);
+                continue;
+            }
+        };
+        backoff = BACKOFF_MIN;
+        if reconnect {
+            info!("live listener reconnected, resyncing");
+            resync(&st, &mut last_iss_id).await;
+        }
+        reconnect = true;
+
+        loop {
+            let msg = tokio::select! {
+                _ = shutdown.cancelled() => return,
+                m = listener.try_recv() => m,
+            };
+            match msg {
+                Ok(Some(n)) => dispatch(&st, &n, &mut last_iss_id),
+                // `None` — соединение потеряно; переподключаемся сами, чтобы знать момент для resync
+                Ok(None) => {
+                    warn!("live listener: connection lost");
+                    break;
+                }
+                Err(e) => {
+                    warn!("live listener: {e:#}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn connect(st: &AppState) -> anyhow::Result<PgListener> {
+    let mut listener = PgListener::connect_with(&st.pool).await?;
+    listener.listen_all(Channel::NOTIFY.iter().map(|c| c.as_str())).await?;
+    Ok(listener)
+}
+
+fn dispatch(st: &AppState, n: &PgNotification, last_iss_id: &mut i64) {
+    if let Some(frame) = to_frame(n.channel(), n.payload(), last_iss_id) {
+        debug!(channel = frame.channel.as_str(), id = frame.id, "live frame");
+        st.live.publish(frame);
+    }
+}
+
+/// Уведомление -> кадр. `None` — чужой канал, битый payload или позиция, которая уже ушла
+fn to_frame(channel: &str, payload: &str, last_iss_id: &mut i64) -> Option<Frame> {
+    let channel = Channel::parse(channel)?;
+    let payload: Value = match serde_json::from_str(payload) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!(channel = channel.as_str(), "live listener: bad payload: {e}");
+            return None;
+        }
+    };
+    let id = payload["id"].as_i64().unwrap_or_default();
+    let json = match channel {
+        Channel::IssPosition => {
+            // та же строка могла уже уйти при resync
+            if id <= *last_iss_id {
+                return None;
+            }
+            *last_iss_id = id;
+            payload["position"].to_string()
+        }
+        _ => payload.to_string(),
+    };
+    Some(Frame { channel, id, json: json.into() })
+}
+
+/// Уведомления за время обрыва не придут: последняя позиция берётся из БД,
+/// а остальные подписчики по кадру `resync` сами перечитывают то, что им нужно
+async fn resync(st: &AppState, last_iss_id: &mut i64) {
+    match st.repos.iss.last().await {
+        Ok(Some(r)) if r.id > *last_iss_id => {
+            if let Some(p) = &r.position {
+                *last_iss_id = r.id;
+                st.live.publish(Frame::position(r.id, p));
+            }
+        }
+        Ok(_) => {}
+        Err(e) => warn!("live listener: resync failed: {e:#}"),
+    }
+    st.live.publish(Frame { channel: Channel::Resync, id: *last_iss_id, json: r#"{"reason":"listener reconnected"}"#.into() });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_parse_by_notify_name() {
+        for c in Channel::NOTIFY {
+            assert_eq!(Channel::parse(c.as_str()), Some(c));
+        }
+        // resync — только внутренний кадр, из Postgres он прийти не может
+        assert_eq!(Channel::parse("resync"), None);
+        assert_eq!(Channel::parse("ISS_POSITION"), None);
+    }
+
+    #[test]
+    fn position_frames_are_deduplicated_by_id() {
+        let mut last = 0;
+        let f = to_frame("iss_position", r#"{"id": 5, "position": {"lat": 1.5}}"#, &mut last).unwrap();
+        assert_eq!((f.channel, f.id, &*f.json), (Channel::IssPosition, 5, r#"{"lat":1.5}"#));
+        assert_eq!(last, 5);
+        // повтор и более старая строка (уже ушли при resync) не рассылаются
+        assert!(to_frame("iss_position", r#"{"id": 5, "position": {}}"#, &mut last).is_none());
+        assert!(to_frame("iss_position", r#"{"id": 3, "position": {}}"#, &mut last).is_none());
+        assert_eq!(to_frame("iss_position", r#"{"id": 6, "position": {}}"#, &mut last).map(|f| f.id), Some(6));
+        assert_eq!(last, 6);
+    }
+
+    #[test]
+    fn other_channels_pass_payload_through() {
+        let mut last = 10;
+        let f = to_frame("space_cache", r#"{"id": 2, "source": "apod"}"#, &mut last).unwrap();
+        assert_eq!((f.channel, f.id, &*f.json), (Channel::SpaceCache, 2, r#"{"id":2,"source":"apod"}"#));
+        let f = to_frame("osdr_item", r#"{"id": 1, "dataset_id": "OSD-1"}"#, &mut last).unwrap();
+        assert_eq!(f.channel, Channel::OsdrItem);
+        // id других каналов на дедупликацию позиций не влияет
+        assert_eq!(last, 10);
+    }
+
+    #[test]
+    fn bad_notifications_are_ignored() {
+        let mut last = 0;
+        assert!(to_frame("unknown", r#"{"id": 1}"#, &mut last).is_none());
+        assert!(to_frame("iss_position", "not json", &mut last).is_none());
+        assert!(to_frame("space_cache", "", &mut last).is_none());
+        assert_eq!(last, 0);
+    }
+}
@@ -0,0 +1,97 @@
+//! Живая лента новых данных. Репозитории шлют `NOTIFY` в Postgres, [`listener`] каждой реплики
+//! принимает их и раздаёт своим SSE/WebSocket-подписчикам — так данные доходят до клиентов любой реплики.
+
+pub mod listener;
+
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use crate::domain::IssPosition;
+
+/// Сколько кадров может отстать подписчик, прежде чем его отключат
+const BUFFER: usize = 64;
+
+/// Канал `NOTIFY` и тип кадра
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// `{"id", "position"}` — новая строка `iss_fetch_log`
+    IssPosition,
+    /// `{"id", "source", "fetched_at"}` — новый снимок в `space_cache` (сам снимок в NOTIFY не влезает)
+    SpaceCache,
+    /// `{"id", "dataset_id"}` — вставка или обновление в `osdr_items`
+    OsdrItem,
+    /// Не канал Postgres: слушатель переподключился, уведомления за время обрыва могли потеряться
+    Resync,
+}
+
+impl Channel {
+    pub const NOTIFY: [Channel; 3] = [Channel::IssPosition, Channel::SpaceCache, Channel::OsdrItem];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Channel::IssPosition => "iss_position",
+            Channel::SpaceCache => "space_cache",
+            Channel::OsdrItem => "osdr_item",
+            Channel::Resync => "resync",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::NOTIFY.into_iter().find(|c| c.as_str() == s)
+    }
+}
+
+/// Кадр, сериализованный один раз на всех подписчиков; `id` — id строки в БД
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub channel: Channel,
+    pub id: i64,
+    pub json: Arc<str>,
+}
+
+impl Frame {
+    pub fn position(id: i64, position: &IssPosition) -> Self {
+        let json = serde_json::to_string(position).unwrap_or_else(|_| "{}".into());
+        Self { channel: Channel::IssPosition, id, json: json.into() }
+    }
+}
+
+pub struct LiveFeed {
+    tx: broadcast::Sender<Frame>,
+    closed: CancellationToken,
+}
+
+impl Default for LiveFeed {
+    fn default() -> Self {
+        Self::with_buffer(BUFFER)
+    }
+}
+
+impl LiveFeed {
+    pub fn with_buffer(buffer: usize) -> Self {
+        Self { tx: broadcast::channel(buffer).0, closed: CancellationToken::new() }
+    }
+
+    /// Без подписчиков кадр просто никуда не уходит
+    pub fn publish(&self, frame: Frame) {
+        let _ = self.tx.send(frame);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Frame> {
+        self.tx.subscribe()
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    /// Завершает все потоки — иначе открытые соединения держат graceful shutdown до таймаута
+    pub fn close(&self) {
+        self.closed.cancel();
+    }
+
+    pub fn closed(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.closed.clone().cancelled_owned()
+    }
+}
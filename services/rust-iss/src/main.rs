@@ -1,17 +1,30 @@
+mod clients;
 mod config;
 mod domain;
+mod errors;
+mod geo;
 mod handlers;
+mod live;
+mod middleware;
+mod migrations;
+mod orbit;
 mod repositories;
 mod routes;
+mod scheduler;
 mod services;
+#[cfg(test)]
+mod testing;
 
-use std::time::Duration;
+use std::future::IntoFuture;
+use std::sync::Arc;
 use sqlx::postgres::PgPoolOptions;
-use tracing::{error, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
-use crate::config::{AppState, env_u64};
-use crate::repositories::IssRepository;
-use crate::services::IssService;
+use crate::clients::Clients;
+use crate::config::{AppState, Settings};
+use crate::repositories::Repos;
+use crate::scheduler::jobs::default_registry;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -22,99 +35,125 @@ async fn main() -> anyhow::Result<()> {
 
     dotenvy::dotenv().ok();
 
-    let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL is required");
+    // все ошибки конфигурации выводятся разом, до подключения к БД
+    let cfg = Arc::new(Settings::load()?);
+    cfg.log_report();
 
-    let nasa_url = std::env::var("NASA_API_URL")
-        .unwrap_or_else(|_| "https://visualization.osdr.nasa.gov/biodata/api/v2/datasets/?format=json".to_string());
-    let nasa_key = std::env::var("NASA_API_KEY").unwrap_or_default();
+    let pool = PgPoolOptions::new().max_connections(cfg.db_max_connections).connect(&cfg.database_url).await?;
 
-    let fallback_url = std::env::var("WHERE_ISS_URL")
-        .unwrap_or_else(|_| "https://api.wheretheiss.at/v1/satellites/25544".to_string());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("migrate") {
+        return migrate_cmd(&pool, &args[1..]).await;
+    }
+    migrations::check_on_start(&pool, cfg.migrate_on_start).await?;
 
-    let every_osdr   = env_u64("FETCH_EVERY_SECONDS", 600);
-    let every_iss    = env_u64("ISS_EVERY_SECONDS",   120);
-    let every_apod   = env_u64("APOD_EVERY_SECONDS",  43200); // 12ч
-    let every_neo    = env_u64("NEO_EVERY_SECONDS",   7200);  // 2ч
-    let every_donki  = env_u64("DONKI_EVERY_SECONDS", 3600);  // 1ч
-    let every_spacex = env_u64("SPACEX_EVERY_SECONDS",3600);
+    let clients = Clients::new(cfg.clients_config())?;
 
-    let pool = PgPoolOptions::new().max_connections(5).connect(&db_url).await?;
-    IssRepository::init_db(&pool).await?;
+    let repos = Repos::pg(&pool);
+    let jobs = Arc::new(default_registry(&cfg));
+    jobs.restore(&*repos.jobs).await?;
 
     let state = AppState {
         pool: pool.clone(),
-        nasa_url: nasa_url.clone(),
-        nasa_key,
-        fallback_url: fallback_url.clone(),
-        every_osdr, every_iss, every_apod, every_neo, every_donki, every_spacex,
+        repos,
+        clients,
+        jobs: jobs.clone(),
+        settings: cfg.clone(),
+        passes: Arc::default(),
+        live: Arc::default(),
+        refresh: Arc::default(),
     };
+    let live = state.live.clone();
+    let shutdown = CancellationToken::new();
+    jobs.spawn_all(&state, shutdown.child_token());
+    tokio::spawn(live::listener::run(state.clone(), shutdown.child_token()));
+    tokio::spawn(wait_for_signal(shutdown.clone()));
 
-    // Background Tasks
-    // OSDR
-    {
-        let st = state.clone();
-        tokio::spawn(async move {
-            loop {
-                if let Err(e) = IssService::fetch_and_store_osdr(&st).await { error!("osdr err {e:?}") }
-                tokio::time::sleep(Duration::from_secs(st.every_osdr)).await;
-            }
-        });
-    }
-    // ISS
-    {
-        let st = state.clone();
-        tokio::spawn(async move {
-            loop {
-                if let Err(e) = IssService::fetch_and_store_iss(&st.pool, &st.fallback_url).await { error!("iss err {e:?}") }
-                tokio::time::sleep(Duration::from_secs(st.every_iss)).await;
-            }
-        });
-    }
-    // APOD
-    {
-        let st = state.clone();
-        tokio::spawn(async move {
-            loop {
-                if let Err(e) = IssService::fetch_apod(&st).await { error!("apod err {e:?}") }
-                tokio::time::sleep(Duration::from_secs(st.every_apod)).await;
-            }
-        });
-    }
-    // NeoWs
-    {
-        let st = state.clone();
-        tokio::spawn(async move {
-            loop {
-                if let Err(e) = IssService::fetch_neo_feed(&st).await { error!("neo err {e:?}") }
-                tokio::time::sleep(Duration::from_secs(st.every_neo)).await;
-            }
-        });
-    }
-    // DONKI
-    {
-        let st = state.clone();
-        tokio::spawn(async move {
-            loop {
-                if let Err(e) = IssService::fetch_donki(&st).await { error!("donki err {e:?}") }
-                tokio::time::sleep(Duration::from_secs(st.every_donki)).await;
-            }
-        });
-    }
-    // SpaceX
-    {
-        let st = state.clone();
-        tokio::spawn(async move {
-            loop {
-                if let Err(e) = IssService::fetch_spacex_next(&st).await { error!("spacex err {e:?}") }
-                tokio::time::sleep(Duration::from_secs(st.every_spacex)).await;
+    let app = routes::app_router(state);
+
+    let listener = tokio::net::TcpListener::bind(cfg.bind_addr).await?;
+    info!("rust_iss listening on {}", cfg.bind_addr);
+
+    let grace = cfg.shutdown_timeout;
+    let server = axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+        .into_future();
+    tokio::pin!(server);
+    let http = tokio::select! {
+        // при одновременной готовности сначала штатная остановка
+        biased;
+        _ = shutdown.cancelled() => {
+            info!("shutdown: draining http connections and jobs (timeout {}s)", grace.as_secs());
+            live.close();
+            let (http, _) = tokio::join!(
+                tokio::time::timeout(grace, &mut server),
+                jobs.drain(grace),
+            );
+            http.unwrap_or_else(|_| {
+                warn!("http connections did not close in {}s", grace.as_secs());
+                Ok(())
+            })
+        }
+        res = &mut server => {
+            // сервер завершился сам (например, ошибкой) — задачи и подписчиков всё равно останавливаем
+            warn!("http server stopped unexpectedly, draining jobs (timeout {}s)", grace.as_secs());
+            shutdown.cancel();
+            live.close();
+            jobs.drain(grace).await;
+            res
+        }
+    };
+
+    pool.close().await;
+    http?;
+    info!("rust_iss stopped");
+    Ok(())
+}
+
+/// `rust_iss migrate [up | down [N] | status]`
+async fn migrate_cmd(pool: &sqlx::PgPool, args: &[String]) -> anyhow::Result<()> {
+    match args.first().map(String::as_str).unwrap_or("up") {
+        "up" => {
+            let n = migrations::up(pool).await?;
+            println!("applied {n} migration(s)");
+        }
+        "down" => {
+            let steps = args.get(1).map(|s| s.parse()).transpose()?.unwrap_or(1);
+            let n = migrations::down(pool, steps).await?;
+            println!("reverted {n} migration(s)");
+        }
+        "status" => {
+            for (version, name, applied) in migrations::status(pool).await? {
+                match applied {
+                    Some(a) => println!("{version:04} {name:<24} applied {} {}", a.applied_at, &a.checksum[..12]),
+                    None => println!("{version:04} {name:<24} pending"),
+                }
             }
-        });
+        }
+        other => anyhow::bail!("unknown migrate command {other:?}; expected up | down [N] | status"),
     }
+    pool.close().await;
+    Ok(())
+}
 
-    let app = routes::app_router(state);
+/// SIGINT (Ctrl+C) или SIGTERM (`docker compose stop`)
+async fn wait_for_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await { warn!("ctrl_c handler failed: {e}") }
+    };
+    #[cfg(unix)]
+    let term = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut s) => { s.recv().await; }
+            Err(e) => { warn!("SIGTERM handler failed: {e}"); std::future::pending::<()>().await }
+        }
+    };
+    #[cfg(not(unix))]
+    let term = std::future::pending::<()>();
 
-    let listener = tokio::net::TcpListener::bind(("0.0.0.0", 3000)).await?;
-    info!("rust_iss listening on 0.0.0.0:3000");
-    axum::serve(listener, app.into_make_service()).await?;
-    Ok(())
-}
\ No newline at end of file
+    tokio::select! {
+        _ = ctrl_c => info!("SIGINT received"),
+        _ = term => info!("SIGTERM received"),
+    }
+    shutdown.cancel();
+}
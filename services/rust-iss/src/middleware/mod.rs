@@ -0,0 +1,83 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{info, info_span, Instrument};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// ID текущего запроса/запуска задачи (если мы внутри `with_request_id`)
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+pub fn new_request_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// Выполнить future с заданным trace id: он попадёт в span, в логи и в `ApiError`
+pub async fn with_request_id<F: std::future::Future>(id: String, span: tracing::Span, fut: F) -> F::Output {
+    REQUEST_ID.scope(id, fut.instrument(span)).await
+}
+
+/// `tokio::spawn` внутри запроса: сама задача не наследует ни task-local request id, ни span — передаём их явно
+pub fn spawn_in_request<F>(fut: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let id = current_request_id().unwrap_or_else(new_request_id);
+    tokio::spawn(with_request_id(id, tracing::Span::current(), fut))
+}
+
+/// Отдельный trace id для каждого запуска фоновой задачи
+pub async fn with_job_trace<F: std::future::Future>(job: &'static str, fut: F) -> F::Output {
+    let id = new_request_id();
+    let span = info_span!("job", job, request_id = %id);
+    with_request_id(id, span, fut).await
+}
+
+/// Принимает `X-Request-Id` от клиента (nginx/Laravel) или генерирует новый
+pub async fn request_id(req: Request, next: Next) -> Response {
+    let id = req.headers().get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|s| !s.is_empty() && s.len() <= 128 && s.chars().all(|c| c.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(new_request_id);
+
+    let span = info_span!("request", request_id = %id, method = %req.method(), path = %req.uri().path());
+    let started = std::time::Instant::now();
+
+    let mut resp = with_request_id(id.clone(), span.clone(), next.run(req)).await;
+
+    span.in_scope(|| info!(status = resp.status().as_u16(), elapsed_ms = started.elapsed().as_millis() as u64, "request done"));
+    if let Ok(v) = HeaderValue::from_str(&id) {
+        resp.headers_mut().insert(REQUEST_ID_HEADER, v);
+    }
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spawned_task_keeps_request_id() {
+        let (outer, inner) = with_job_trace("test", async {
+            let inner = spawn_in_request(async { current_request_id() }).await.unwrap();
+            (current_request_id(), inner)
+        }).await;
+        assert!(outer.is_some());
+        assert_eq!(outer, inner);
+        // голый spawn, созданный внутри области, его теряет
+        let lost = with_job_trace("test", async { tokio::spawn(async { current_request_id() }).await.unwrap() }).await;
+        assert_eq!(lost, None);
+    }
+}
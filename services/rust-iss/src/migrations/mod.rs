@@ -0,0 +1,264 @@
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, PgConnection, PgPool, Row};
+use tracing::{info, warn};
+
+/// Миграции вшиты в бинарник: `migrations/NNNN_name.{up,down}.sql`
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+macro_rules! migration {
+    ($version:literal, $file:literal, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("../../migrations/", $file, "_", $name, ".up.sql")),
+            down: include_str!(concat!("../../migrations/", $file, "_", $name, ".down.sql")),
+        }
+    };
+}
+
+/// Новая миграция = пара файлов + строка здесь (версии строго по возрастанию)
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001", "init"),
+    migration!(2, "0002", "job_runs"),
+    migration!(3, "0003", "iss_position"),
+    migration!(4, "0004", "tle"),
+    migration!(5, "0005", "iss_retention"),
+    migration!(6, "0006", "space_cache_dedup"),
+];
+
+/// Ключ advisory-lock'а, чтобы две реплики не мигрировали одновременно
+const LOCK_KEY: i64 = 0x1557_0000_0001;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrateError {
+    #[error("database schema has migration {version} ({name}) unknown to this binary; refusing to start against a newer schema")]
+    UnknownVersion { version: i64, name: String },
+    #[error("checksum mismatch for migration {version} ({name}): applied {applied}, file {expected}")]
+    ChecksumMismatch { version: i64, name: String, applied: String, expected: String },
+    #[error("{0} pending migration(s); run `rust_iss migrate up` or enable MIGRATE_ON_START")]
+    Pending(usize),
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+pub struct Applied {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+    pub applied_at: chrono::DateTime<chrono::Utc>,
+}
+
+fn checksum(sql: &str) -> String {
+    hex::encode(Sha256::digest(sql.as_bytes()))
+}
+
+fn known(version: i64) -> Option<&'static Migration> {
+    MIGRATIONS.iter().find(|m| m.version == version)
+}
+
+async fn ensure_table(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS _migrations(
+            version BIGINT PRIMARY KEY,
+            name TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )"
+    ).await?;
+    Ok(())
+}
+
+async fn applied(conn: &mut PgConnection) -> Result<Vec<Applied>, sqlx::Error> {
+    let rows = sqlx::query("SELECT version, name, checksum, applied_at FROM _migrations ORDER BY version")
+        .fetch_all(&mut *conn).await?;
+    Ok(rows.into_iter().map(|r| Applied {
+        version: r.get("version"),
+        name: r.get("name"),
+        checksum: r.get("checksum"),
+        applied_at: r.get("applied_at"),
+    }).collect())
+}
+
+/// Проверка применённых миграций против вшитых: неизвестные версии и изменённые файлы — ошибка
+fn verify(done: &[Applied]) -> Result<(), MigrateError> {
+    for a in done {
+        let Some(m) = known(a.version) else {
+            return Err(MigrateError::UnknownVersion { version: a.version, name: a.name.clone() });
+        };
+        let expected = checksum(m.up);
+        if a.checksum != expected {
+            return Err(MigrateError::ChecksumMismatch {
+                version: a.version, name: a.name.clone(), applied: a.checksum.clone(), expected,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Вшитые миграции, которых нет среди применённых, по возрастанию версии
+fn pending(done: &[Applied]) -> impl Iterator<Item = &'static Migration> + '_ {
+    MIGRATIONS.iter().filter(|m| !done.iter().any(|a| a.version == m.version))
+}
+
+/// Что откатывать для `down steps`: последние применённые, от новой к старой (`done` уже прошёл [`verify`])
+fn revert_plan(done: &[Applied], steps: usize) -> Vec<&'static Migration> {
+    done.iter().rev().take(steps).map(|a| known(a.version).expect("verified above")).collect()
+}
+
+/// Выполнить `f` под advisory lock на отдельном соединении
+async fn with_lock<T>(pool: &PgPool, f: impl AsyncFnOnce(&mut PgConnection) -> Result<T, MigrateError>) -> Result<T, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    sqlx::query("SELECT pg_advisory_lock($1)").bind(LOCK_KEY).execute(&mut *conn).await?;
+    let res = async {
+        ensure_table(&mut conn).await?;
+        f(&mut conn).await
+    }.await;
+    if let Err(e) = sqlx::query("SELECT pg_advisory_unlock($1)").bind(LOCK_KEY).execute(&mut *conn).await {
+        warn!("migrations unlock failed: {e}");
+        let _ = conn.detach();
+    }
+    res
+}
+
+/// Применить все неприменённые миграции (каждая — в своей транзакции)
+pub async fn up(pool: &PgPool) -> Result<usize, MigrateError> {
+    with_lock(pool, async |conn: &mut PgConnection| {
+        let done = applied(conn).await?;
+        verify(&done)?;
+        let mut n = 0;
+        for m in pending(&done) {
+            info!(version = m.version, name = m.name, "applying migration");
+            let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+            tx.execute(m.up).await?;
+            sqlx::query("INSERT INTO _migrations(version, name, checksum) VALUES ($1, $2, $3)")
+                .bind(m.version).bind(m.name).bind(checksum(m.up))
+                .execute(&mut *tx).await?;
+            tx.commit().await?;
+            n += 1;
+        }
+        Ok(n)
+    }).await
+}
+
+/// Откатить `steps` последних применённых миграций
+pub async fn down(pool: &PgPool, steps: usize) -> Result<usize, MigrateError> {
+    with_lock(pool, async move |conn: &mut PgConnection| {
+        let done = applied(conn).await?;
+        verify(&done)?;
+        let mut n = 0;
+        for m in revert_plan(&done, steps) {
+            info!(version = m.version, name = m.name, "reverting migration");
+            let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+            tx.execute(m.down).await?;
+            sqlx::query("DELETE FROM _migrations WHERE version = $1").bind(m.version).execute(&mut *tx).await?;
+            tx.commit().await?;
+            n += 1;
+        }
+        Ok(n)
+    }).await
+}
+
+/// Список миграций: применённые с датой, остальные — pending
+pub async fn status(pool: &PgPool) -> Result<Vec<(i64, &'static str, Option<Applied>)>, MigrateError> {
+    with_lock(pool, async |conn: &mut PgConnection| {
+        let mut done = applied(conn).await?;
+        verify(&done)?;
+        Ok(MIGRATIONS.iter().map(|m| {
+            let a = done.iter().position(|a| a.version == m.version).map(|i| done.swap_remove(i));
+            (m.version, m.name, a)
+        }).collect())
+    }).await
+}
+
+/// Проверка при старте сервиса: схема не новее бинарника; pending либо применяем, либо отказываемся стартовать
+pub async fn check_on_start(pool: &PgPool, apply: bool) -> Result<(), MigrateError> {
+    if apply {
+        let n = up(pool).await?;
+        info!("migrations: {n} applied, schema at version {}", MIGRATIONS.last().map_or(0, |m| m.version));
+        return Ok(());
+    }
+    let pending = status(pool).await?.iter().filter(|(_, _, a)| a.is_none()).count();
+    if pending > 0 {
+        return Err(MigrateError::Pending(pending));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(version: i64, checksum: String) -> Applied {
+        Applied { version, name: known(version).map_or("gone", |m| m.name).into(), checksum, applied_at: chrono::Utc::now() }
+    }
+
+    fn all_applied() -> Vec<Applied> {
+        MIGRATIONS.iter().map(|m| row(m.version, checksum(m.up))).collect()
+    }
+
+    #[test]
+    fn embedded_migrations_are_ordered_and_complete() {
+        assert!(MIGRATIONS.windows(2).all(|w| w[0].version < w[1].version));
+        for m in MIGRATIONS {
+            assert!(!m.up.trim().is_empty() && !m.down.trim().is_empty(), "migration {} has an empty file", m.version);
+        }
+    }
+
+    #[test]
+    fn checksum_is_sha256_hex_of_up_sql() {
+        assert_eq!(checksum(""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_ne!(checksum("SELECT 1;"), checksum("SELECT 1; "));
+    }
+
+    #[test]
+    fn verify_accepts_untouched_history() {
+        verify(&all_applied()).unwrap();
+        verify(&[]).unwrap();
+    }
+
+    #[test]
+    fn verify_detects_edited_migration() {
+        let mut done = all_applied();
+        done[0].checksum = checksum("-- edited after release");
+        match verify(&done) {
+            Err(MigrateError::ChecksumMismatch { version, applied, expected, .. }) => {
+                assert_eq!(version, MIGRATIONS[0].version);
+                assert_eq!(applied, checksum("-- edited after release"));
+                assert_eq!(expected, checksum(MIGRATIONS[0].up));
+            }
+            other => panic!("expected ChecksumMismatch, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn verify_rejects_schema_newer_than_binary() {
+        let mut done = all_applied();
+        done.push(row(9999, "x".into()));
+        assert!(matches!(verify(&done), Err(MigrateError::UnknownVersion { version: 9999, .. })));
+    }
+
+    #[test]
+    fn pending_skips_applied_versions() {
+        let done = vec![row(1, checksum(MIGRATIONS[0].up))];
+        let versions: Vec<i64> = pending(&done).map(|m| m.version).collect();
+        assert_eq!(versions, MIGRATIONS[1..].iter().map(|m| m.version).collect::<Vec<_>>());
+        assert_eq!(pending(&all_applied()).count(), 0);
+    }
+
+    #[test]
+    fn down_reverts_newest_first() {
+        let done = all_applied();
+        let newest: Vec<i64> = MIGRATIONS.iter().rev().map(|m| m.version).collect();
+        let plan = |steps| revert_plan(&done, steps).iter().map(|m| m.version).collect::<Vec<_>>();
+        assert_eq!(plan(1), newest[..1]);
+        assert_eq!(plan(2), newest[..2]);
+        // больше, чем применено, — откатываем всё, без ошибки
+        assert_eq!(plan(100), newest);
+        assert!(plan(0).is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+pub mod passes;
+pub mod sgp4;
+pub mod tle;
+
+use std::f64::consts::PI;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use crate::domain::{IssPosition, PositionSource};
+use crate::geo::EARTH_RADIUS_KM;
+use sgp4::{Sgp4, Sgp4Error, StateVector};
+use tle::Tle;
+
+/// Каталожный номер NORAD МКС (ZARYA)
+pub const ISS_NORAD_ID: u32 = 25544;
+
+/// TLE + инициализированная модель: для серии моментов времени (`/iss/predict`, пролёты) инициализация одна
+pub struct Propagator {
+    pub tle: Tle,
+    model: Sgp4,
+}
+
+impl Propagator {
+    pub fn new(tle: Tle) -> Result<Self, Sgp4Error> {
+        let model = Sgp4::new(&tle)?;
+        Ok(Self { tle, model })
+    }
+
+    /// Состояние в TEME на момент `at`
+    pub fn state_at(&self, at: DateTime<Utc>) -> Result<StateVector, Sgp4Error> {
+        let minutes = (at - self.tle.epoch).num_milliseconds() as f64 / 60_000.0;
+        self.model.propagate(minutes)
+    }
+
+    /// Подспутниковая точка, высота и скорость в тех же единицах, что отдаёт wheretheiss.at
+    pub fn position_at(&self, at: DateTime<Utc>) -> Result<IssPosition, Sgp4Error> {
+        let sv = self.state_at(at)?;
+        let ecef = teme_to_ecef(sv.r, at);
+        let (lat, lon, alt) = geodetic(ecef);
+        let speed_km_s = (sv.v[0].powi(2) + sv.v[1].powi(2) + sv.v[2].powi(2)).sqrt();
+        Ok(IssPosition {
+            lat,
+            lon,
+            altitude_km: Some(alt),
+            velocity_kmh: Some(speed_km_s * 3600.0),
+            visibility: None,
+            footprint_km: Some(footprint_km(alt)),
+            solar_lat: None,
+            solar_lon: None,
+            timestamp: at,
+            source: PositionSource::Predicted,
+        })
+    }
+}
+
+/// Последний момент, на который TLE ещё годится для прогноза
+pub fn tle_valid_until(tle: &Tle, max_age: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(max_age).ok()
+        .and_then(|d| tle.epoch.checked_add_signed(d))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+/// Ошибка SGP4 растёт с удалением от эпохи — дальше `max_age` в любую сторону не считаем
+pub fn check_tle_age(tle: &Tle, at: DateTime<Utc>, max_age: Duration) -> Result<(), String> {
+    let age = (at - tle.epoch).abs();
+    if age.to_std().unwrap_or(Duration::MAX) > max_age {
+        return Err(format!(
+            "{at} is {:.1} days from TLE epoch {} (limit {:.1} days)",
+            age.num_seconds() as f64 / 86400.0, tle.epoch, max_age.as_secs_f64() / 86400.0,
+        ));
+    }
+    Ok(())
+}
+
+/// Гринвичское среднее звёздное время (IAU-82), радианы
+pub fn gmst(at: DateTime<Utc>) -> f64 {
+    let jd = at.timestamp_millis() as f64 / 86_400_000.0 + 2_440_587.5;
+    let tut1 = (jd - 2_451_545.0) / 36_525.0;
+    let secs = -6.2e-6 * tut1.powi(3) + 0.093104 * tut1 * tut1
+        + (876600.0 * 3600.0 + 8640184.812866) * tut1 + 67310.54841;
+    (secs * PI / 180.0 / 240.0).rem_euclid(2.0 * PI)
+}
+
+/// TEME -> ECEF поворотом на GMST (движение полюса не учитываем — это метры)
+pub fn teme_to_ecef(r: [f64; 3], at: DateTime<Utc>) -> [f64; 3] {
+    let (s, c) = gmst(at).sin_cos();
+    [c * r[0] + s * r[1], -s * r[0] + c * r[1], r[2]]
+}
+
+/// ECEF (км) -> геодезические широта/долгота (градусы) и высота (км) над эллипсоидом WGS-84
+pub fn geodetic(p: [f64; 3]) -> (f64, f64, f64) {
+    const A: f64 = 6378.137;
+    const F: f64 = 1.0 / 298.257223563;
+    let e2 = F * (2.0 - F);
+    let lon = p[1].atan2(p[0]);
+    let rho = (p[0] * p[0] + p[1] * p[1]).sqrt();
+    let mut lat = p[2].atan2(rho * (1.0 - e2));
+    let mut h = 0.0;
+    for _ in 0..6 {
+        let n = A / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+        h = rho / lat.cos() - n;
+        lat = p[2].atan2(rho * (1.0 - e2 * n / (n + h)));
+    }
+    (lat.to_degrees(), lon.to_degrees(), h)
+}
+
+/// Диаметр зоны видимости с высоты `alt_km` (как `footprint` у wheretheiss.at)
+fn footprint_km(alt_km: f64) -> f64 {
+    2.0 * EARTH_RADIUS_KM * (EARTH_RADIUS_KM / (EARTH_RADIUS_KM + alt_km)).acos()
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use crate::domain::{IssPass, PassPoint};
+use crate::geo::haversine_km;
+use super::sgp4::{Sgp4Error, RADIUS_EARTH_KM};
+use super::{geodetic, teme_to_ecef, Propagator};
+
+/// Шаг грубого поиска: пролёт МКС длится 4–10 минут, 30 с его не пропустят
+const SCAN_STEP_S: i64 = 30;
+/// Наблюдатель в темноте — Солнце ниже гражданских сумерек
+const DARK_SUN_ELEVATION_DEG: f64 = -6.0;
+const AU_KM: f64 = 149_597_870.7;
+
+/// Точка наблюдения: геодезические широта/долгота (градусы), высота над эллипсоидом (м)
+#[derive(Clone, Copy, Debug)]
+pub struct Observer {
+    pub lat: f64,
+    pub lon: f64,
+    pub alt_m: f64,
+}
+
+impl Observer {
+    /// Округление до 0.01° (~1 км) и 10 м — столько же, сколько различает кэш
+    pub fn rounded(lat: f64, lon: f64, alt_m: f64) -> Self {
+        Self { lat: (lat * 100.0).round() / 100.0, lon: (lon * 100.0).round() / 100.0, alt_m: (alt_m / 10.0).round() * 10.0 }
+    }
+
+    fn ecef(&self) -> [f64; 3] {
+        const A: f64 = 6378.137;
+        const F: f64 = 1.0 / 298.257223563;
+        let e2 = F * (2.0 - F);
+        let (sl, cl) = self.lat.to_radians().sin_cos();
+        let (so, co) = self.lon.to_radians().sin_cos();
+        let n = A / (1.0 - e2 * sl * sl).sqrt();
+        let h = self.alt_m / 1000.0;
+        [(n + h) * cl * co, (n + h) * cl * so, (n * (1.0 - e2) + h) * sl]
+    }
+
+    /// Азимут (от севера по часовой) и угол места точки `p` (ECEF, км), градусы
+    fn look(&self, p: [f64; 3]) -> (f64, f64) {
+        let o = self.ecef();
+        let d = [p[0] - o[0], p[1] - o[1], p[2] - o[2]];
+        let (sl, cl) = self.lat.to_radians().sin_cos();
+        let (so, co) = self.lon.to_radians().sin_cos();
+        let south = sl * co * d[0] + sl * so * d[1] - cl * d[2];
+        let east = -so * d[0] + co * d[1];
+        let zenith = cl * co * d[0] + cl * so * d[1] + sl * d[2];
+        let range = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+        let az = east.atan2(-south).to_degrees().rem_euclid(360.0);
+        (az, (zenith / range).asin().to_degrees())
+    }
+}
+
+/// Направление на Солнце в экваториальной системе даты (≈ TEME), км.
+/// Малоточный алгоритм Astronomical Almanac — ~0.01°, для тени и сумерек достаточно.
+fn sun_eci(at: DateTime<Utc>) -> [f64; 3] {
+    let n = at.timestamp_millis() as f64 / 86_400_000.0 + 2_440_587.5 - 2_451_545.0;
+    let l = (280.460 + 0.9856474 * n).to_radians();
+    let g = (357.528 + 0.9856003 * n).to_radians();
+    let lambda = l + (1.915f64.to_radians()) * g.sin() + (0.020f64.to_radians()) * (2.0 * g).sin();
+    let eps = (23.439 - 0.0000004 * n).to_radians();
+    let r = (1.00014 - 0.01671 * g.cos() - 0.00014 * (2.0 * g).cos()) * AU_KM;
+    [r * lambda.cos(), r * eps.cos() * lambda.sin(), r * eps.sin() * lambda.sin()]
+}
+
+/// Цилиндрическая модель тени Земли
+fn sunlit(sat: [f64; 3], sun: [f64; 3]) -> bool {
+    let sn = (sun[0] * sun[0] + sun[1] * sun[1] + sun[2] * sun[2]).sqrt();
+    let s = [sun[0] / sn, sun[1] / sn, sun[2] / sn];
+    let along = sat[0] * s[0] + sat[1] * s[1] + sat[2] * s[2];
+    if along > 0.0 {
+        return true;
+    }
+    let perp = [sat[0] - along * s[0], sat[1] - along * s[1], sat[2] - along * s[2]];
+    (perp[0] * perp[0] + perp[1] * perp[1] + perp[2] * perp[2]).sqrt() > RADIUS_EARTH_KM
+}
+
+struct Sky<'a> {
+    prop: &'a Propagator,
+    obs: Observer,
+}
+
+impl Sky<'_> {
+    fn look(&self, t: DateTime<Utc>) -> Result<(f64, f64), Sgp4Error> {
+        let sv = self.prop.state_at(t)?;
+        Ok(self.obs.look(teme_to_ecef(sv.r, t)))
+    }
+
+    fn elevation(&self, t: DateTime<Utc>) -> Result<f64, Sgp4Error> {
+        Ok(self.look(t)?.1)
+    }
+
+    fn point(&self, t: DateTime<Utc>) -> Result<PassPoint, Sgp4Error> {
+        let (az, el) = self.look(t)?;
+        Ok(PassPoint { at: t, azimuth_deg: az, elevation_deg: el })
+    }
+
+    /// Момент пересечения горизонта между `a` (ниже/выше) и `b` (наоборот), с точностью до секунды
+    fn horizon(&self, mut a: DateTime<Utc>, mut b: DateTime<Utc>) -> Result<DateTime<Utc>, Sgp4Error> {
+        let rising = self.elevation(a)? < 0.0;
+        while b - a > Duration::seconds(1) {
+            let mid = a + (b - a) / 2;
+            if (self.elevation(mid)? < 0.0) == rising { a = mid } else { b = mid }
+        }
+        Ok(b)
+    }
+
+    /// Максимум угла места на отрезке (функция унимодальна в пределах пролёта)
+    fn culmination(&self, mut a: DateTime<Utc>, mut b: DateTime<Utc>) -> Result<DateTime<Utc>, Sgp4Error> {
+        while b - a > Duration::seconds(1) {
+            let m1 = a + (b - a) / 3;
+            let m2 = b - (b - a) / 3;
+            if self.elevation(m1)? < self.elevation(m2)? { a = m1 } else { b = m2 }
+        }
+        Ok(a + (b - a) / 2)
+    }
+
+    /// Видим ли пролёт глазом: хотя бы в одной точке МКС освещена, а у наблюдателя темно
+    fn visible(&self, rise: DateTime<Utc>, set: DateTime<Utc>) -> Result<bool, Sgp4Error> {
+        let mut t = rise;
+        while t <= set {
+            let sun = sun_eci(t);
+            let sun_el = self.obs.look(teme_to_ecef(sun, t)).1;
+            if sun_el < DARK_SUN_ELEVATION_DEG && sunlit(self.prop.state_at(t)?.r, sun) {
+                return Ok(true);
+            }
+            t += Duration::seconds(10);
+        }
+        Ok(false)
+    }
+}
+
+/// Все пролёты над горизонтом с восходом в `[from, to)`. Дальше `stop` модель не считаем (годность TLE):
+/// пролёт, который к этому моменту не закончился, отбрасывается.
+pub fn find_passes(prop: &Propagator, obs: Observer, from: DateTime<Utc>, to: DateTime<Utc>, stop: DateTime<Utc>) -> Result<Vec<IssPass>, Sgp4Error> {
+    let sky = Sky { prop, obs };
+    let step = Duration::seconds(SCAN_STEP_S);
+    let mut passes = Vec::new();
+    let mut t = from;
+    let mut prev = sky.elevation(t)?;
+    // пролёт, уже идущий в момент `from`, не попадёт в результат — у него нет восхода в окне
+    let mut rise: Option<DateTime<Utc>> = None;
+    let mut best = (t, f64::MIN);
+
+    // идущий на границе `to` пролёт досчитываем до захода (пролёт МКС заведомо короче часа)
+    let hard_stop = (to + Duration::hours(1)).min(stop);
+    while (t < to || rise.is_some()) && t < hard_stop {
+        let next = (t + step).min(hard_stop);
+        let el = sky.elevation(next)?;
+        if prev < 0.0 && el >= 0.0 && next <= to {
+            rise = Some(sky.horizon(t, next)?);
+            best = (next, el);
+        } else if el >= 0.0 && el > best.1 {
+            best = (next, el);
+        }
+        if prev >= 0.0 && el < 0.0 {
+            if let Some(r) = rise.take() {
+                let set = sky.horizon(t, next)?;
+                let top = sky.culmination((best.0 - step).max(r), (best.0 + step).min(set))?;
+                let culmination = sky.point(top)?;
+                let (sub_lat, sub_lon, _) = geodetic(teme_to_ecef(prop.state_at(top)?.r, top));
+                passes.push(IssPass {
+                    rise: sky.point(r)?,
+                    max_elevation_deg: culmination.elevation_deg,
+                    culmination,
+                    set: sky.point(set)?,
+                    duration_s: (set - r).num_seconds(),
+                    visible: sky.visible(r, set)?,
+                    min_ground_distance_km: haversine_km(obs.lat, obs.lon, sub_lat, sub_lon),
+                });
+            }
+            best = (next, f64::MIN);
+        }
+        prev = el;
+        t = next;
+    }
+    Ok(passes)
+}
+
+/// Ключ кэша: округлённая точка, сутки UTC, длина окна и эпоха TLE (новый TLE — новый расчёт)
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct PassKey {
+    lat_e2: i32,
+    lon_e2: i32,
+    alt_m: i32,
+    day: NaiveDate,
+    days: u32,
+    tle_epoch: i64,
+}
+
+/// Пролёты по округлённой точке и суткам; записи за прошедшие сутки выбрасываются при вставке
+#[derive(Default)]
+pub struct PassCache {
+    entries: Mutex<HashMap<PassKey, Arc<Vec<IssPass>>>>,
+}
+
+/// Больше разных точек за сутки не держим — это защита от перебора координат
+const PASS_CACHE_MAX: usize = 1024;
+
+impl PassCache {
+    pub fn get_or_compute(
+        &self,
+        obs: Observer,
+        day: NaiveDate,
+        days: u32,
+        stop: DateTime<Utc>,
+        prop: &Propagator,
+    ) -> Result<Arc<Vec<IssPass>>, Sgp4Error> {
+        let key = PassKey {
+            lat_e2: (obs.lat * 100.0).round() as i32,
+            lon_e2: (obs.lon * 100.0).round() as i32,
+            alt_m: obs.alt_m.round() as i32,
+            day,
+            days,
+            tle_epoch: prop.tle.epoch.timestamp(),
+        };
+        if let Some(hit) = self.entries.lock().unwrap().get(&key) {
+            return Ok(hit.clone());
+        }
+        let from = day.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+        let passes = Arc::new(find_passes(prop, obs, from, from + Duration::days(days as i64), stop)?);
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|k, _| k.day >= day);
+        if entries.len() >= PASS_CACHE_MAX {
+            entries.clear();
+        }
+        entries.insert(key, passes.clone());
+        Ok(passes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orbit::{tle::Tle, tle_valid_until};
+    use crate::testing::ISS_TLE;
+
+    fn setup() -> (Propagator, Observer, DateTime<Utc>) {
+        let tle = Tle::from_lines(None, ISS_TLE[0], ISS_TLE[1]).unwrap();
+        let from = tle.epoch;
+        (Propagator::new(tle).unwrap(), Observer::rounded(55.75, 37.62, 150.0), from)
+    }
+
+    #[test]
+    fn passes_are_ordered_and_consistent() {
+        let (prop, obs, from) = setup();
+        let to = from + Duration::days(1);
+        let passes = find_passes(&prop, obs, from, to, to + Duration::hours(1)).unwrap();
+        assert!(!passes.is_empty());
+        for p in &passes {
+            assert!(p.rise.at >= from && p.rise.at < to);
+            assert!(p.rise.at < p.culmination.at && p.culmination.at < p.set.at);
+            assert!(p.max_elevation_deg > 0.0 && p.max_elevation_deg <= 90.0);
+            assert!(p.rise.elevation_deg.abs() < 0.5 && p.set.elevation_deg.abs() < 0.5);
+            assert!((60..=900).contains(&p.duration_s), "{}", p.duration_s);
+        }
+        assert!(passes.windows(2).all(|w| w[0].set.at < w[1].rise.at));
+    }
+
+    #[test]
+    fn pass_crossing_stop_is_dropped() {
+        let (prop, obs, from) = setup();
+        let day = from + Duration::days(1);
+        let all = find_passes(&prop, obs, from, day, day).unwrap();
+        let p = &all[all.len() / 2];
+        // окно кончается посреди пролёта: с запасом до захода он досчитывается
+        let to = p.rise.at + Duration::seconds(60);
+        let tail = find_passes(&prop, obs, from, to, to + Duration::hours(1)).unwrap();
+        assert_eq!(tail.last().map(|x| x.set.at), Some(p.set.at));
+        // а если TLE годна только до конца окна — за `stop` не считаем и пролёт отбрасываем
+        let cut = find_passes(&prop, obs, from, to, to).unwrap();
+        assert_eq!(cut.len(), tail.len() - 1);
+        assert!(cut.iter().all(|x| x.set.at <= to));
+    }
+
+    #[test]
+    fn valid_until_follows_max_age() {
+        let (prop, _, _) = setup();
+        let week = std::time::Duration::from_secs(7 * 86400);
+        assert_eq!(tle_valid_until(&prop.tle, week), prop.tle.epoch + Duration::days(7));
+        assert_eq!(tle_valid_until(&prop.tle, std::time::Duration::MAX), DateTime::<Utc>::MAX_UTC);
+    }
+}
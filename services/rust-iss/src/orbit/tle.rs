@@ -0,0 +1,216 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+/// Один набор элементов (Two-Line Element set) в исходных единицах TLE
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tle {
+    pub name: Option<String>,
+    pub norad_id: u32,
+    pub epoch: DateTime<Utc>,
+    /// Коэффициент торможения B*, 1/радиус Земли
+    pub bstar: f64,
+    pub inclination_deg: f64,
+    pub raan_deg: f64,
+    pub eccentricity: f64,
+    pub arg_perigee_deg: f64,
+    pub mean_anomaly_deg: f64,
+    /// Среднее движение, оборотов в сутки
+    pub mean_motion_rev_day: f64,
+    pub line1: String,
+    pub line2: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TleError {
+    #[error("no TLE found in input")]
+    Empty,
+    #[error("TLE line {line}: {reason}")]
+    Format { line: u8, reason: String },
+    #[error("TLE line {line}: checksum mismatch (expected {expected}, got {got})")]
+    Checksum { line: u8, expected: u32, got: u32 },
+}
+
+impl Tle {
+    /// Разбор текста из CelesTrak/файла: 2LE или 3LE (строка имени перед элементами).
+    /// Если наборов несколько, берётся первый с нужным `norad_id` (или просто первый).
+    pub fn parse_first(text: &str, norad_id: Option<u32>) -> Result<Self, TleError> {
+        let lines: Vec<&str> = text.lines().map(str::trim_end).filter(|l| !l.trim().is_empty()).collect();
+        let mut first_err = None;
+        for (i, l) in lines.iter().enumerate() {
+            if !l.starts_with("1 ") || i + 1 >= lines.len() || !lines[i + 1].starts_with("2 ") {
+                continue;
+            }
+            let name = i.checked_sub(1)
+                .map(|j| lines[j])
+                .filter(|n| !n.starts_with("1 ") && !n.starts_with("2 "))
+                .map(|n| n.trim_start_matches("0 ").trim().to_string());
+            match Self::from_lines(name, l, lines[i + 1]) {
+                Ok(t) if norad_id.is_none_or(|id| id == t.norad_id) => return Ok(t),
+                Ok(_) => {}
+                Err(e) => { first_err.get_or_insert(e); }
+            }
+        }
+        Err(first_err.unwrap_or(TleError::Empty))
+    }
+
+    pub fn from_lines(name: Option<String>, line1: &str, line2: &str) -> Result<Self, TleError> {
+        for (n, l) in [(1u8, line1), (2, line2)] {
+            if l.len() < 69 || !l.is_ascii() {
+                return Err(TleError::Format { line: n, reason: format!("expected 69 ASCII columns, got {}", l.len()) });
+            }
+            let expected = checksum(&l[..68]);
+            let got = l.as_bytes()[68].wrapping_sub(b'0') as u32;
+            if expected != got {
+                return Err(TleError::Checksum { line: n, expected, got });
+            }
+        }
+
+        let norad_id = field::<u32>(line1, 1, 2, 7)?;
+        if field::<u32>(line2, 2, 2, 7)? != norad_id {
+            return Err(TleError::Format { line: 2, reason: "catalog number differs from line 1".into() });
+        }
+        let year = field::<i32>(line1, 1, 18, 20)?;
+        let day = field::<f64>(line1, 1, 20, 32)?;
+        // двузначный год: 57..99 -> 19xx, 00..56 -> 20xx (как в спецификации NORAD)
+        let year = if year < 57 { 2000 + year } else { 1900 + year };
+        let jan1 = Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).single()
+            .ok_or_else(|| TleError::Format { line: 1, reason: format!("bad epoch year {year}") })?;
+        let epoch = jan1 + Duration::microseconds(((day - 1.0) * 86_400e6).round() as i64);
+
+        Ok(Self {
+            name,
+            norad_id,
+            epoch,
+            bstar: implied_exp(&line1[53..61]).ok_or_else(|| TleError::Format { line: 1, reason: "bad B* drag term".into() })?,
+            inclination_deg: field(line2, 2, 8, 16)?,
+            raan_deg: field(line2, 2, 17, 25)?,
+            eccentricity: format!("0.{}", line2[26..33].trim()).parse()
+                .map_err(|_| TleError::Format { line: 2, reason: "bad eccentricity".into() })?,
+            arg_perigee_deg: field(line2, 2, 34, 42)?,
+            mean_anomaly_deg: field(line2, 2, 43, 51)?,
+            mean_motion_rev_day: field(line2, 2, 52, 63)?,
+            line1: line1.to_string(),
+            line2: line2.to_string(),
+        })
+    }
+}
+
+/// Контрольная сумма строки TLE: сумма цифр, «-» считается за 1, по модулю 10
+fn checksum(s: &str) -> u32 {
+    s.bytes().map(|b| match b {
+        b'0'..=b'9' => (b - b'0') as u32,
+        b'-' => 1,
+        _ => 0,
+    }).sum::<u32>() % 10
+}
+
+fn field<T: std::str::FromStr>(l: &str, line: u8, from: usize, to: usize) -> Result<T, TleError> {
+    l[from..to].trim().parse().map_err(|_| TleError::Format {
+        line,
+        reason: format!("columns {}-{}: cannot parse {:?}", from + 1, to, &l[from..to]),
+    })
+}
+
+/// Формат «мантисса с подразумеваемой точкой + порядок»: ` 28098-4` = 0.28098e-4
+fn implied_exp(s: &str) -> Option<f64> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Some(0.0);
+    }
+    let (mant, exp) = s.split_at(s.len().checked_sub(2)?);
+    let (sign, digits) = match mant.strip_prefix('-') {
+        Some(d) => (-1.0, d),
+        None => (1.0, mant.trim_start_matches('+')),
+    };
+    let m: f64 = format!("0.{digits}").parse().ok()?;
+    let e: i32 = exp.parse().ok()?;
+    Some(sign * m * 10f64.powi(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Vallado, SGP4-VER.TLE
+    const L1: &str = "1 00005U 58002B   00179.78495062  .00000023  00000-0  28098-4 0  4753";
+    const L2: &str = "2 00005  34.2682 348.7242 1859667 331.7664  19.3264 10.82419157413667";
+
+    /// Подменить колонки и пересчитать контрольную сумму
+    fn patched(l: &str, at: usize, s: &str) -> String {
+        let body = format!("{}{}{}", &l[..at], s, &l[at + s.len()..68]);
+        format!("{body}{}", checksum(&body))
+    }
+
+    #[test]
+    fn parses_reference_elements() {
+        let t = Tle::from_lines(None, L1, L2).unwrap();
+        assert_eq!(t.norad_id, 5);
+        assert_eq!(t.epoch, Utc.with_ymd_and_hms(2000, 6, 27, 18, 50, 19).unwrap() + Duration::microseconds(733_568));
+        assert!((t.bstar - 0.28098e-4).abs() < 1e-15);
+        assert_eq!(t.inclination_deg, 34.2682);
+        assert_eq!(t.raan_deg, 348.7242);
+        assert_eq!(t.eccentricity, 0.1859667);
+        assert_eq!(t.arg_perigee_deg, 331.7664);
+        assert_eq!(t.mean_anomaly_deg, 19.3264);
+        assert_eq!(t.mean_motion_rev_day, 10.82419157);
+    }
+
+    #[test]
+    fn checksum_counts_minus_as_one() {
+        assert_eq!(checksum(&L1[..68]), 3);
+        assert_eq!(checksum(&L2[..68]), 7);
+        assert_eq!(checksum("1-2-"), 5);
+        assert_eq!(checksum("A B+."), 0);
+    }
+
+    #[test]
+    fn rejects_bad_checksum_and_short_lines() {
+        let bad = format!("{}4", &L1[..68]);
+        match Tle::from_lines(None, &bad, L2) {
+            Err(TleError::Checksum { line: 1, expected: 3, got: 4 }) => {}
+            other => panic!("expected checksum error, got {other:?}"),
+        }
+        assert!(matches!(Tle::from_lines(None, &L1[..60], L2), Err(TleError::Format { line: 1, .. })));
+        let other_sat = patched(L2, 2, "00006");
+        assert!(matches!(Tle::from_lines(None, L1, &other_sat), Err(TleError::Format { line: 2, .. })));
+    }
+
+    #[test]
+    fn implied_exponent_fields() {
+        let close = |s: &str, want: f64| (implied_exp(s).unwrap() - want).abs() < 1e-15;
+        assert!(close(" 28098-4", 0.28098e-4));
+        assert!(close("-11606-4", -0.11606e-4));
+        assert!(close("+12345+1", 1.2345));
+        assert_eq!(implied_exp(" 00000-0"), Some(0.0));
+        assert_eq!(implied_exp(" 00000+0"), Some(0.0));
+        assert_eq!(implied_exp("        "), Some(0.0));
+        assert_eq!(implied_exp(" 1234x-4"), None);
+        // отрицательный B* в строке целиком
+        let t = Tle::from_lines(None, &patched(L1, 53, "-11606-4"), L2).unwrap();
+        assert!((t.bstar + 0.11606e-4).abs() < 1e-15);
+    }
+
+    #[test]
+    fn two_digit_year_pivot() {
+        let year = |yy: &str| Tle::from_lines(None, &patched(L1, 18, yy), L2).unwrap().epoch;
+        // 1957 не високосный: 179-й день — 28 июня
+        assert_eq!(year("57"), Utc.with_ymd_and_hms(1957, 6, 28, 18, 50, 19).unwrap() + Duration::microseconds(733_568));
+        assert_eq!(year("99").format("%Y").to_string(), "1999");
+        assert_eq!(year("00").format("%Y").to_string(), "2000");
+        assert_eq!(year("56").format("%Y").to_string(), "2056");
+        // день 1.5 — полдень 1 января
+        let noon = Tle::from_lines(None, &patched(L1, 18, "24001.50000000"), L2).unwrap();
+        assert_eq!(noon.epoch, Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn parse_first_picks_named_set_by_norad_id() {
+        let iss1 = patched(L1, 2, "25544");
+        let iss2 = patched(L2, 2, "25544");
+        let text = format!("0 VANGUARD 1\n{L1}\n{L2}\n\nISS (ZARYA)\n{iss1}\n{iss2}\n");
+        let t = Tle::parse_first(&text, Some(25544)).unwrap();
+        assert_eq!((t.norad_id, t.name.as_deref()), (25544, Some("ISS (ZARYA)")));
+        let first = Tle::parse_first(&text, None).unwrap();
+        assert_eq!((first.norad_id, first.name.as_deref()), (5, Some("VANGUARD 1")));
+        assert!(matches!(Tle::parse_first("nothing here", None), Err(TleError::Empty)));
+    }
+}
@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use sqlx::{pool::PoolConnection, PgPool, Postgres, Row};
+use chrono::{DateTime, Utc};
+use crate::domain::JobStatus;
+use tracing::{instrument, warn};
+use super::{JobLock, JobLocks, JobRepo};
+
+pub struct PgJobRepo(pub PgPool);
+
+#[async_trait]
+impl JobRepo for PgJobRepo {
+    #[instrument(skip_all, level = "debug", name = "repo.job_register", fields(job = name))]
+    async fn register(&self, name: &str, interval_seconds: i64) -> anyhow::Result<bool> {
+        let row = sqlx::query(
+            "INSERT INTO job_runs(name, interval_seconds) VALUES ($1, $2)
+             ON CONFLICT (name) DO UPDATE SET interval_seconds = EXCLUDED.interval_seconds
+             RETURNING paused"
+        ).bind(name).bind(interval_seconds).fetch_one(&self.0).await?;
+        Ok(row.get("paused"))
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.job_started", fields(job = name))]
+    async fn mark_started(&self, name: &str) -> anyhow::Result<()> {
+        sqlx::query("UPDATE job_runs SET last_started = now(), run_count = run_count + 1 WHERE name = $1")
+            .bind(name).execute(&self.0).await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.job_finished", fields(job = name))]
+    async fn mark_finished(&self, name: &str, error: Option<String>) -> anyhow::Result<()> {
+        sqlx::query("UPDATE job_runs SET last_finished = now(), last_error = $2 WHERE name = $1")
+            .bind(name).bind(error).execute(&self.0).await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.job_next_run", fields(job = name))]
+    async fn set_next_run(&self, name: &str, at: DateTime<Utc>) -> anyhow::Result<()> {
+        sqlx::query("UPDATE job_runs SET next_run_at = $2 WHERE name = $1")
+            .bind(name).bind(at).execute(&self.0).await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.job_paused", fields(job = name))]
+    async fn set_paused(&self, name: &str, paused: bool) -> anyhow::Result<()> {
+        sqlx::query("UPDATE job_runs SET paused = $2 WHERE name = $1")
+            .bind(name).bind(paused).execute(&self.0).await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.job_list")]
+    async fn list(&self) -> anyhow::Result<Vec<JobStatus>> {
+        let rows = sqlx::query(
+            "SELECT name, interval_seconds, paused, run_count, last_started, last_finished, last_error, next_run_at
+             FROM job_runs ORDER BY name"
+        ).fetch_all(&self.0).await?;
+
+        Ok(rows.into_iter().map(|r| JobStatus {
+            name: r.get("name"),
+            interval_seconds: r.get("interval_seconds"),
+            paused: r.get("paused"),
+            running: false,
+            run_count: r.get("run_count"),
+            last_started: r.get("last_started"),
+            last_finished: r.get("last_finished"),
+            last_error: r.get("last_error"),
+            next_run_at: r.get("next_run_at"),
+        }).collect())
+    }
+}
+
+/// Пространство ключей advisory-lock'ов rust_iss (первый int4 в двухаргументной форме)
+const LOCK_NAMESPACE: i32 = 0x1557;
+
+/// `pg_try_advisory_lock` по имени задачи: общая блокировка для всех реплик
+pub struct PgJobLocks(pub PgPool);
+
+#[async_trait]
+impl JobLocks for PgJobLocks {
+    async fn try_lock(&self, job: &str) -> anyhow::Result<Option<Box<dyn JobLock>>> {
+        // advisory lock живёт в сессии, поэтому соединение держим до unlock
+        let mut conn = self.0.acquire().await?;
+        let locked: bool = sqlx::query("SELECT pg_try_advisory_lock($1, hashtext($2)) AS ok")
+            .bind(LOCK_NAMESPACE).bind(job)
+            .fetch_one(&mut *conn).await?
+            .get("ok");
+        Ok(locked.then(|| Box::new(PgJobLock { job: job.to_string(), conn: Some(conn) }) as Box<dyn JobLock>))
+    }
+}
+
+struct PgJobLock {
+    job: String,
+    conn: Option<PoolConnection<Postgres>>,
+}
+
+#[async_trait]
+impl JobLock for PgJobLock {
+    async fn unlock(mut self: Box<Self>) {
+        let Some(mut conn) = self.conn.take() else { return };
+        let unlocked = sqlx::query("SELECT pg_advisory_unlock($1, hashtext($2))")
+            .bind(LOCK_NAMESPACE).bind(&self.job)
+            .execute(&mut *conn).await;
+        if let Err(e) = unlocked {
+            // не возвращаем соединение с висящей блокировкой в пул
+            warn!(job = self.job, "advisory unlock failed: {e}");
+            let _ = conn.detach();
+        }
+    }
+}
+
+impl Drop for PgJobLock {
+    fn drop(&mut self) {
+        // прогон отменили до unlock: закрываем сессию, вместе с ней снимается и блокировка
+        if let Some(conn) = self.conn.take() {
+            let _ = conn.detach();
+        }
+    }
+}
@@ -0,0 +1,371 @@
+//! Репозитории в памяти процесса: детерминированные, без Postgres — для тестов обработчиков и сервисов
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use chrono::{DateTime, Utc};
+use crate::domain::{CachePruned, CacheSourceStats, IssFetch, IssPosition, IssRollup, JobStatus, PositionSource, Resolution, RetentionReport, SpaceCacheItem, SpaceCacheVersion};
+use crate::geo::trend::stats;
+use crate::orbit::tle::Tle;
+use super::{CacheRepo, IssRepo, JobLock, JobLocks, JobRepo, OsdrRepo, TleRepo};
+
+#[derive(Default)]
+pub struct MemIssRepo {
+    rows: Mutex<Vec<IssFetch>>,
+}
+
+#[async_trait]
+impl IssRepo for MemIssRepo {
+    async fn log_fetch(&self, url: &str, position: &IssPosition, payload: Value) -> anyhow::Result<()> {
+        let mut rows = self.rows.lock().unwrap();
+        let id = rows.len() as i64 + 1;
+        rows.push(IssFetch { id, fetched_at: Utc::now(), source_url: url.to_string(), payload, position: Some(position.clone()) });
+        Ok(())
+    }
+
+    async fn last(&self) -> anyhow::Result<Option<IssFetch>> {
+        Ok(self.rows.lock().unwrap().last().cloned())
+    }
+
+    async fn trend_data(&self) -> anyhow::Result<Vec<(DateTime<Utc>, IssPosition)>> {
+        let rows = self.rows.lock().unwrap();
+        Ok(rows.iter().rev().filter_map(|r| Some((r.fetched_at, r.position.clone()?))).take(2).collect())
+    }
+
+    async fn track(&self, from: DateTime<Utc>, to: DateTime<Utc>, max_points: usize) -> anyhow::Result<Vec<IssPosition>> {
+        let bucket_ms = ((to - from).num_milliseconds() / max_points.max(1) as i64).max(1);
+        let mut points: Vec<IssPosition> = self.rows.lock().unwrap().iter()
+            .filter_map(|r| r.position.clone())
+            .filter(|p| p.timestamp >= from && p.timestamp <= to)
+            .collect();
+        points.sort_by_key(|p| p.timestamp);
+        let last_bucket = max_points as i64 - 1;
+        points.dedup_by_key(|p| ((p.timestamp - from).num_milliseconds() / bucket_ms).min(last_bucket));
+        Ok(points)
+    }
+
+    async fn window(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> anyhow::Result<Vec<IssPosition>> {
+        let mut points: Vec<IssPosition> = self.rows.lock().unwrap().iter()
+            .filter_map(|r| r.position.clone())
+            .filter(|p| p.timestamp >= from && p.timestamp <= to)
+            .collect();
+        points.sort_by_key(|p| p.timestamp);
+        Ok(points)
+    }
+
+    /// Агрегаты считаются на лету из строк — отдельного хранилища нет
+    async fn rollups(&self, resolution: Resolution, from: DateTime<Utc>, to: DateTime<Utc>) -> anyhow::Result<Vec<IssRollup>> {
+        let secs = resolution.bucket_seconds();
+        anyhow::ensure!(secs > 0, "raw positions have no rollups");
+        let bucket = |p: &IssPosition| p.timestamp.timestamp().div_euclid(secs) * secs;
+        let positions = self.window(from - chrono::Duration::seconds(secs), to).await?;
+        Ok(positions.chunk_by(|a, b| bucket(a) == bucket(b))
+            .filter_map(|chunk| {
+                let start = DateTime::from_timestamp(bucket(&chunk[0]), 0)?;
+                if start <= from - chrono::Duration::seconds(secs) || start > to {
+                    return None;
+                }
+                let observed: Vec<&IssPosition> = chunk.iter().filter(|p| p.source == PositionSource::Observed).collect();
+                Some(IssRollup {
+                    bucket: start,
+                    samples: observed.len() as i64,
+                    predicted_samples: (chunk.len() - observed.len()) as i64,
+                    altitude_km: stats(observed.iter().filter_map(|p| p.altitude_km)),
+                    velocity_kmh: stats(observed.iter().filter_map(|p| p.velocity_kmh)),
+                    first_at: chunk[0].timestamp,
+                    lat: chunk[0].lat,
+                    lon: chunk[0].lon,
+                })
+            })
+            .collect())
+    }
+
+    async fn ensure_partitions(&self, _months_ahead: u32) -> anyhow::Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    async fn rollup(&self) -> anyhow::Result<(u64, u64)> {
+        Ok((0, 0))
+    }
+
+    /// Агрегаты здесь не хранятся, поэтому сырые строки режутся ровно по `raw_before`
+    async fn prune(&self, raw_before: DateTime<Utc>, _hourly_before: DateTime<Utc>) -> anyhow::Result<RetentionReport> {
+        let mut rows = self.rows.lock().unwrap();
+        let before = rows.len();
+        rows.retain(|r| r.fetched_at >= raw_before);
+        Ok(RetentionReport { raw_rows_deleted: (before - rows.len()) as u64, ..Default::default() })
+    }
+}
+
+struct OsdrRow {
+    id: i64,
+    dataset_id: Option<String>,
+    title: Option<String>,
+    status: Option<String>,
+    updated_at: Option<DateTime<Utc>>,
+    inserted_at: DateTime<Utc>,
+    raw: Value,
+}
+
+#[derive(Default)]
+pub struct MemOsdrRepo {
+    rows: Mutex<Vec<OsdrRow>>,
+}
+
+#[async_trait]
+impl OsdrRepo for MemOsdrRepo {
+    async fn upsert(&self, dataset_id: Option<String>, title: Option<String>, status: Option<String>, updated_at: Option<DateTime<Utc>>, raw: Value) -> anyhow::Result<()> {
+        let mut rows = self.rows.lock().unwrap();
+        if let Some(r) = rows.iter_mut().find(|r| dataset_id.is_some() && r.dataset_id == dataset_id) {
+            (r.title, r.status, r.updated_at, r.raw) = (title, status, updated_at, raw);
+            return Ok(());
+        }
+        let id = rows.len() as i64 + 1;
+        rows.push(OsdrRow { id, dataset_id, title, status, updated_at, inserted_at: Utc::now(), raw });
+        Ok(())
+    }
+
+    async fn list(&self, limit: i64) -> anyhow::Result<Vec<Value>> {
+        let rows = self.rows.lock().unwrap();
+        Ok(rows.iter().rev().take(limit.max(0) as usize).map(|r| {
+            serde_json::json!({
+                "id": r.id,
+                "dataset_id": r.dataset_id,
+                "title": r.title,
+                "status": r.status,
+                "updated_at": r.updated_at,
+                "inserted_at": r.inserted_at,
+                "raw": r.raw,
+            })
+        }).collect())
+    }
+
+    async fn count(&self) -> anyhow::Result<i64> {
+        Ok(self.rows.lock().unwrap().len() as i64)
+    }
+}
+
+/// Накопленные счётчики источника, как в `space_cache_stats`
+#[derive(Default, Clone)]
+struct CacheCounters {
+    dedup_hits: i64,
+    rows_pruned: i64,
+    bytes_reclaimed: i64,
+    last_pruned_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+pub struct MemCacheRepo {
+    items: Mutex<Vec<SpaceCacheItem>>,
+    counters: Mutex<BTreeMap<String, CacheCounters>>,
+}
+
+/// Размер снимка — длина его JSON; в Postgres считается `pg_column_size`
+fn payload_bytes(payload: &Value) -> i64 {
+    payload.to_string().len() as i64
+}
+
+#[async_trait]
+impl CacheRepo for MemCacheRepo {
+    async fn write(&self, source: &str, payload: Value) -> anyhow::Result<bool> {
+        let mut items = self.items.lock().unwrap();
+        let now = Utc::now();
+        if let Some(last) = items.iter_mut().rev().find(|i| i.source == source).filter(|i| i.payload == payload) {
+            last.last_seen_at = now;
+            self.counters.lock().unwrap().entry(source.to_string()).or_default().dedup_hits += 1;
+            return Ok(false);
+        }
+        let id = items.last().map_or(1, |i| i.id + 1);
+        items.push(SpaceCacheItem { id, source: source.to_string(), fetched_at: now, last_seen_at: now, payload });
+        Ok(true)
+    }
+
+    async fn latest(&self, source: &str) -> anyhow::Result<Option<SpaceCacheItem>> {
+        Ok(self.items.lock().unwrap().iter().rev().find(|i| i.source == source).cloned())
+    }
+
+    async fn version(&self, source: &str, id: i64) -> anyhow::Result<Option<SpaceCacheItem>> {
+        Ok(self.items.lock().unwrap().iter().find(|i| i.source == source && i.id == id).cloned())
+    }
+
+    async fn previous(&self, source: &str, id: i64) -> anyhow::Result<Option<SpaceCacheItem>> {
+        Ok(self.items.lock().unwrap().iter().rev().find(|i| i.source == source && i.id < id).cloned())
+    }
+
+    async fn history(&self, source: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>, limit: i64) -> anyhow::Result<Vec<SpaceCacheVersion>> {
+        let items = self.items.lock().unwrap();
+        Ok(items.iter().rev()
+            .filter(|i| i.source == source)
+            .filter(|i| from.is_none_or(|t| i.fetched_at >= t) && to.is_none_or(|t| i.fetched_at <= t))
+            .take(limit.max(0) as usize)
+            .map(|i| SpaceCacheVersion {
+                id: i.id,
+                fetched_at: i.fetched_at,
+                last_seen_at: i.last_seen_at,
+                // тот же sha256, но от компактного JSON, а не от текста jsonb — с хешами Postgres не сравнивать
+                content_hash: hex::encode(Sha256::digest(i.payload.to_string())),
+                bytes: payload_bytes(&i.payload),
+            })
+            .collect())
+    }
+
+    async fn prune(&self, source: &str, keep_versions: Option<u32>, older_than: Option<DateTime<Utc>>) -> anyhow::Result<CachePruned> {
+        let mut items = self.items.lock().unwrap();
+        let mut rank = items.iter().filter(|i| i.source == source).count();
+        let mut report = CachePruned { source: source.to_string(), ..Default::default() };
+        // rank — номер версии от новой к старой, как row_number() в Postgres
+        items.retain(|i| {
+            if i.source != source {
+                return true;
+            }
+            let rn = rank;
+            rank -= 1;
+            let drop = rn > 1
+                && keep_versions.is_none_or(|n| rn > n as usize)
+                && older_than.is_none_or(|t| i.fetched_at < t);
+            if drop {
+                report.rows += 1;
+                report.bytes += payload_bytes(&i.payload) as u64;
+            }
+            !drop
+        });
+        let mut counters = self.counters.lock().unwrap();
+        let c = counters.entry(source.to_string()).or_default();
+        c.rows_pruned += report.rows as i64;
+        c.bytes_reclaimed += report.bytes as i64;
+        c.last_pruned_at = Some(Utc::now());
+        Ok(report)
+    }
+
+    async fn stats(&self) -> anyhow::Result<Vec<CacheSourceStats>> {
+        let items = self.items.lock().unwrap();
+        let mut counters = self.counters.lock().unwrap().clone();
+        for i in items.iter() {
+            counters.entry(i.source.clone()).or_default();
+        }
+        Ok(counters.into_iter().map(|(source, c)| {
+            let versions: Vec<&SpaceCacheItem> = items.iter().filter(|i| i.source == source).collect();
+            CacheSourceStats {
+                versions: versions.len() as i64,
+                bytes: versions.iter().map(|i| payload_bytes(&i.payload)).sum(),
+                oldest: versions.iter().map(|i| i.fetched_at).min(),
+                newest: versions.iter().map(|i| i.fetched_at).max(),
+                last_seen_at: versions.iter().map(|i| i.last_seen_at).max(),
+                source,
+                dedup_hits: c.dedup_hits,
+                rows_pruned: c.rows_pruned,
+                bytes_reclaimed: c.bytes_reclaimed,
+                last_pruned_at: c.last_pruned_at,
+            }
+        }).collect())
+    }
+}
+
+#[derive(Default)]
+pub struct MemJobRepo {
+    jobs: Mutex<BTreeMap<String, JobStatus>>,
+}
+
+impl MemJobRepo {
+    /// Как `UPDATE ... WHERE name = $1`: незарегистрированная задача молча пропускается
+    fn update(&self, name: &str, f: impl FnOnce(&mut JobStatus)) -> anyhow::Result<()> {
+        if let Some(j) = self.jobs.lock().unwrap().get_mut(name) {
+            f(j);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl JobRepo for MemJobRepo {
+    async fn register(&self, name: &str, interval_seconds: i64) -> anyhow::Result<bool> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let j = jobs.entry(name.to_string()).or_insert_with(|| JobStatus {
+            name: name.to_string(),
+            interval_seconds,
+            paused: false,
+            running: false,
+            run_count: 0,
+            last_started: None,
+            last_finished: None,
+            last_error: None,
+            next_run_at: None,
+        });
+        j.interval_seconds = interval_seconds;
+        Ok(j.paused)
+    }
+
+    async fn mark_started(&self, name: &str) -> anyhow::Result<()> {
+        self.update(name, |j| (j.last_started, j.run_count) = (Some(Utc::now()), j.run_count + 1))
+    }
+
+    async fn mark_finished(&self, name: &str, error: Option<String>) -> anyhow::Result<()> {
+        self.update(name, |j| (j.last_finished, j.last_error) = (Some(Utc::now()), error))
+    }
+
+    async fn set_next_run(&self, name: &str, at: DateTime<Utc>) -> anyhow::Result<()> {
+        self.update(name, |j| j.next_run_at = Some(at))
+    }
+
+    async fn set_paused(&self, name: &str, paused: bool) -> anyhow::Result<()> {
+        self.update(name, |j| j.paused = paused)
+    }
+
+    async fn list(&self) -> anyhow::Result<Vec<JobStatus>> {
+        Ok(self.jobs.lock().unwrap().values().cloned().collect())
+    }
+}
+
+/// Блокировки внутри процесса: имя задачи занято, пока жива её [`JobLock`]
+#[derive(Default)]
+pub struct MemJobLocks {
+    held: Arc<Mutex<HashSet<String>>>,
+}
+
+#[async_trait]
+impl JobLocks for MemJobLocks {
+    async fn try_lock(&self, job: &str) -> anyhow::Result<Option<Box<dyn JobLock>>> {
+        let locked = self.held.lock().unwrap().insert(job.to_string());
+        Ok(locked.then(|| Box::new(MemJobLock { held: self.held.clone(), job: job.to_string() }) as Box<dyn JobLock>))
+    }
+}
+
+struct MemJobLock {
+    held: Arc<Mutex<HashSet<String>>>,
+    job: String,
+}
+
+#[async_trait]
+impl JobLock for MemJobLock {
+    async fn unlock(self: Box<Self>) {}
+}
+
+impl Drop for MemJobLock {
+    fn drop(&mut self) {
+        self.held.lock().unwrap().remove(&self.job);
+    }
+}
+
+#[derive(Default)]
+pub struct MemTleRepo {
+    sets: Mutex<Vec<Tle>>,
+}
+
+#[async_trait]
+impl TleRepo for MemTleRepo {
+    async fn save(&self, tle: &Tle, _origin: &str) -> anyhow::Result<bool> {
+        let mut sets = self.sets.lock().unwrap();
+        if sets.iter().any(|t| t.norad_id == tle.norad_id && t.epoch == tle.epoch) {
+            return Ok(false);
+        }
+        sets.push(tle.clone());
+        Ok(true)
+    }
+
+    async fn latest(&self, norad_id: u32) -> anyhow::Result<Option<Tle>> {
+        let sets = self.sets.lock().unwrap();
+        Ok(sets.iter().filter(|t| t.norad_id == norad_id).max_by_key(|t| t.epoch).cloned())
+    }
+}
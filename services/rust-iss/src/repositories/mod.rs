@@ -1,150 +1,137 @@
-use sqlx::{PgPool, Row};
+mod jobs;
+#[cfg(test)]
+pub mod memory;
+mod pg;
+
+use std::sync::Arc;
+use async_trait::async_trait;
 use serde_json::Value;
 use chrono::{DateTime, Utc};
-use crate::domain::SpaceCacheItem;
+use sqlx::PgPool;
+use crate::domain::{CachePruned, CacheSourceStats, IssFetch, IssPosition, IssRollup, JobStatus, Resolution, RetentionReport, SpaceCacheItem, SpaceCacheVersion};
+use crate::orbit::tle::Tle;
 
-pub struct IssRepository;
+pub use jobs::{PgJobLocks, PgJobRepo};
+pub use pg::{PgCacheRepo, PgIssRepo, PgOsdrRepo, PgTleRepo};
 
-impl IssRepository {
-    pub async fn init_db(pool: &PgPool) -> anyhow::Result<()> {
-        // ISS
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS iss_fetch_log(
-                id BIGSERIAL PRIMARY KEY,
-                fetched_at TIMESTAMPTZ NOT NULL DEFAULT now(),
-                source_url TEXT NOT NULL,
-                payload JSONB NOT NULL
-            )"
-        ).execute(pool).await?;
+/// Журнал опросов МКС (`iss_fetch_log`)
+#[async_trait]
+pub trait IssRepo: Send + Sync {
+    async fn log_fetch(&self, url: &str, position: &IssPosition, payload: Value) -> anyhow::Result<()>;
+    async fn last(&self) -> anyhow::Result<Option<IssFetch>>;
+    /// Две последние распознанные позиции (с `fetched_at`), новая первой
+    async fn trend_data(&self) -> anyhow::Result<Vec<(DateTime<Utc>, IssPosition)>>;
+    /// Позиции в `[from, to]` по времени измерения, прореженные до `max_points`:
+    /// интервал делится на равные корзины, из каждой берётся первая точка
+    async fn track(&self, from: DateTime<Utc>, to: DateTime<Utc>, max_points: usize) -> anyhow::Result<Vec<IssPosition>>;
+    /// Все позиции в `[from, to]` без прореживания, по возрастанию времени измерения
+    async fn window(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> anyhow::Result<Vec<IssPosition>>;
+    /// Агрегаты с корзинами, пересекающими `[from, to]`, по возрастанию
+    async fn rollups(&self, resolution: Resolution, from: DateTime<Utc>, to: DateTime<Utc>) -> anyhow::Result<Vec<IssRollup>>;
+    /// Секции на текущий месяц и `months_ahead` следующих; возвращает имена созданных
+    async fn ensure_partitions(&self, months_ahead: u32) -> anyhow::Result<Vec<String>>;
+    /// Досчитывает агрегаты с последней (возможно, неполной) корзины; число обновлённых часов и суток
+    async fn rollup(&self) -> anyhow::Result<(u64, u64)>;
+    /// Удаляет сырые строки до `raw_before` (не дальше уже свёрнутого) и почасовые агрегаты до `hourly_before`
+    async fn prune(&self, raw_before: DateTime<Utc>, hourly_before: DateTime<Utc>) -> anyhow::Result<RetentionReport>;
+}
 
-        // OSDR
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS osdr_items(
-                id BIGSERIAL PRIMARY KEY,
-                dataset_id TEXT,
-                title TEXT,
-                status TEXT,
-                updated_at TIMESTAMPTZ,
-                inserted_at TIMESTAMPTZ NOT NULL DEFAULT now(),
-                raw JSONB NOT NULL
-            )"
-        ).execute(pool).await?;
-        sqlx::query(
-            "CREATE UNIQUE INDEX IF NOT EXISTS ux_osdr_dataset_id
-             ON osdr_items(dataset_id) WHERE dataset_id IS NOT NULL"
-        ).execute(pool).await?;
+/// Наборы данных OSDR (`osdr_items`)
+#[async_trait]
+pub trait OsdrRepo: Send + Sync {
+    /// Без `dataset_id` запись просто добавляется, с ним — обновляется по ключу
+    async fn upsert(&self, dataset_id: Option<String>, title: Option<String>, status: Option<String>, updated_at: Option<DateTime<Utc>>, raw: Value) -> anyhow::Result<()>;
+    /// Последние добавленные, новые первыми
+    async fn list(&self, limit: i64) -> anyhow::Result<Vec<Value>>;
+    async fn count(&self) -> anyhow::Result<i64>;
+}
 
-        // Space Cache
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS space_cache(
-                id BIGSERIAL PRIMARY KEY,
-                source TEXT NOT NULL,
-                fetched_at TIMESTAMPTZ NOT NULL DEFAULT now(),
-                payload JSONB NOT NULL
-            )"
-        ).execute(pool).await?;
-        sqlx::query("CREATE INDEX IF NOT EXISTS ix_space_cache_source ON space_cache(source,fetched_at DESC)").execute(pool).await?;
+/// Снимки внешних API (`space_cache`)
+#[async_trait]
+pub trait CacheRepo: Send + Sync {
+    /// `false`, если содержимое совпало с последней версией источника — тогда у неё только обновляется `last_seen_at`
+    async fn write(&self, source: &str, payload: Value) -> anyhow::Result<bool>;
+    async fn latest(&self, source: &str) -> anyhow::Result<Option<SpaceCacheItem>>;
+    /// Версия по id; чужой источник — `None`
+    async fn version(&self, source: &str, id: i64) -> anyhow::Result<Option<SpaceCacheItem>>;
+    /// Версия, сохранённая перед `id`
+    async fn previous(&self, source: &str, id: i64) -> anyhow::Result<Option<SpaceCacheItem>>;
+    /// Версии, впервые полученные в `[from, to]`, новые первыми
+    async fn history(&self, source: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>, limit: i64) -> anyhow::Result<Vec<SpaceCacheVersion>>;
+    /// Удаляет версии сверх `keep_versions` последних и старше `older_than` (нужны оба условия, если заданы оба);
+    /// последняя версия не удаляется никогда
+    async fn prune(&self, source: &str, keep_versions: Option<u32>, older_than: Option<DateTime<Utc>>) -> anyhow::Result<CachePruned>;
+    async fn stats(&self) -> anyhow::Result<Vec<CacheSourceStats>>;
+}
 
-        Ok(())
-    }
+/// Учёт фоновых задач (`job_runs`)
+#[async_trait]
+pub trait JobRepo: Send + Sync {
+    /// Регистрирует задачу при старте и возвращает сохранённый флаг паузы
+    async fn register(&self, name: &str, interval_seconds: i64) -> anyhow::Result<bool>;
+    async fn mark_started(&self, name: &str) -> anyhow::Result<()>;
+    async fn mark_finished(&self, name: &str, error: Option<String>) -> anyhow::Result<()>;
+    async fn set_next_run(&self, name: &str, at: DateTime<Utc>) -> anyhow::Result<()>;
+    async fn set_paused(&self, name: &str, paused: bool) -> anyhow::Result<()>;
+    /// Все зарегистрированные задачи по имени; `running` заполняет реестр процесса
+    async fn list(&self) -> anyhow::Result<Vec<JobStatus>>;
+}
 
-    pub async fn log_iss_fetch(pool: &PgPool, url: &str, payload: Value) -> anyhow::Result<()> {
-        sqlx::query("INSERT INTO iss_fetch_log (source_url, payload) VALUES ($1, $2)")
-            .bind(url).bind(payload).execute(pool).await?;
-        Ok(())
-    }
+/// Блокировки задач, общие для всех реплик
+#[async_trait]
+pub trait JobLocks: Send + Sync {
+    /// Одна попытка взять блокировку `job`; `None` — её держит кто-то другой
+    async fn try_lock(&self, job: &str) -> anyhow::Result<Option<Box<dyn JobLock>>>;
+}
 
-    pub async fn get_last_iss(pool: &PgPool) -> anyhow::Result<Option<(i64, DateTime<Utc>, String, Value)>> {
-        let row_opt = sqlx::query(
-            "SELECT id, fetched_at, source_url, payload
-             FROM iss_fetch_log
-             ORDER BY id DESC LIMIT 1"
-        ).fetch_optional(pool).await?;
+/// Взятая блокировка. Брошенная без `unlock` (future отменили) тоже не должна остаться висеть
+#[async_trait]
+pub trait JobLock: Send {
+    async fn unlock(self: Box<Self>);
+}
 
-        if let Some(row) = row_opt {
-            Ok(Some((
-                row.get("id"),
-                row.get("fetched_at"),
-                row.get("source_url"),
-                row.try_get("payload").unwrap_or(serde_json::json!({})),
-            )))
-        } else {
-            Ok(None)
-        }
-    }
+/// Наборы орбитальных элементов (`tle_sets`)
+#[async_trait]
+pub trait TleRepo: Send + Sync {
+    /// `false`, если набор с такой эпохой уже есть
+    async fn save(&self, tle: &Tle, origin: &str) -> anyhow::Result<bool>;
+    /// Набор с самой свежей эпохой
+    async fn latest(&self, norad_id: u32) -> anyhow::Result<Option<Tle>>;
+}
 
-    pub async fn get_iss_trend_data(pool: &PgPool) -> anyhow::Result<Vec<(DateTime<Utc>, Value)>> {
-        let rows = sqlx::query("SELECT fetched_at, payload FROM iss_fetch_log ORDER BY id DESC LIMIT 2")
-            .fetch_all(pool).await?;
-        
-        Ok(rows.into_iter().map(|r| (r.get("fetched_at"), r.get("payload"))).collect())
-    }
+/// Набор репозиториев, которые получают обработчики и сервисы через `AppState`
+#[derive(Clone)]
+pub struct Repos {
+    pub iss: Arc<dyn IssRepo>,
+    pub osdr: Arc<dyn OsdrRepo>,
+    pub cache: Arc<dyn CacheRepo>,
+    pub jobs: Arc<dyn JobRepo>,
+    pub locks: Arc<dyn JobLocks>,
+    pub tle: Arc<dyn TleRepo>,
+}
 
-    pub async fn upsert_osdr_item(pool: &PgPool, dataset_id: Option<String>, title: Option<String>, status: Option<String>, updated_at: Option<DateTime<Utc>>, raw: Value) -> anyhow::Result<()> {
-        if let Some(ds) = dataset_id {
-            sqlx::query(
-                "INSERT INTO osdr_items(dataset_id, title, status, updated_at, raw)
-                 VALUES($1,$2,$3,$4,$5)
-                 ON CONFLICT (dataset_id) DO UPDATE
-                 SET title=EXCLUDED.title, status=EXCLUDED.status,
-                     updated_at=EXCLUDED.updated_at, raw=EXCLUDED.raw"
-            ).bind(ds).bind(title).bind(status).bind(updated_at).bind(raw).execute(pool).await?;
-        } else {
-            sqlx::query(
-                "INSERT INTO osdr_items(dataset_id, title, status, updated_at, raw)
-                 VALUES($1,$2,$3,$4,$5)"
-            ).bind::<Option<String>>(None).bind(title).bind(status).bind(updated_at).bind(raw).execute(pool).await?;
+impl Repos {
+    pub fn pg(pool: &PgPool) -> Self {
+        Self {
+            iss: Arc::new(PgIssRepo(pool.clone())),
+            osdr: Arc::new(PgOsdrRepo(pool.clone())),
+            cache: Arc::new(PgCacheRepo(pool.clone())),
+            jobs: Arc::new(PgJobRepo(pool.clone())),
+            locks: Arc::new(PgJobLocks(pool.clone())),
+            tle: Arc::new(PgTleRepo(pool.clone())),
         }
-        Ok(())
     }
 
-    pub async fn get_osdr_list(pool: &PgPool, limit: i64) -> anyhow::Result<Vec<Value>> {
-        let rows = sqlx::query(
-            "SELECT id, dataset_id, title, status, updated_at, inserted_at, raw
-             FROM osdr_items
-             ORDER BY inserted_at DESC
-             LIMIT $1"
-        ).bind(limit).fetch_all(pool).await?;
-
-        Ok(rows.into_iter().map(|r| {
-            serde_json::json!({
-                "id": r.get::<i64,_>("id"),
-                "dataset_id": r.get::<Option<String>,_>("dataset_id"),
-                "title": r.get::<Option<String>,_>("title"),
-                "status": r.get::<Option<String>,_>("status"),
-                "updated_at": r.get::<Option<DateTime<Utc>>,_>("updated_at"),
-                "inserted_at": r.get::<DateTime<Utc>, _>("inserted_at"),
-                "raw": r.get::<Value,_>("raw"),
-            })
-        }).collect())
-    }
-    
-    pub async fn get_osdr_count(pool: &PgPool) -> anyhow::Result<i64> {
-        let count: i64 = sqlx::query("SELECT count(*) AS c FROM osdr_items")
-            .fetch_one(pool).await.map(|r| r.get::<i64,_>("c")).unwrap_or(0);
-        Ok(count)
-    }
-
-    pub async fn write_space_cache(pool: &PgPool, source: &str, payload: Value) -> anyhow::Result<()> {
-        sqlx::query("INSERT INTO space_cache(source, payload) VALUES ($1,$2)")
-            .bind(source).bind(payload).execute(pool).await?;
-        Ok(())
-    }
-
-    pub async fn get_latest_space_cache(pool: &PgPool, source: &str) -> anyhow::Result<Option<SpaceCacheItem>> {
-        let row = sqlx::query(
-            "SELECT fetched_at, payload FROM space_cache
-             WHERE source = $1 ORDER BY id DESC LIMIT 1"
-        ).bind(source).fetch_optional(pool).await?;
-
-        if let Some(r) = row {
-            Ok(Some(SpaceCacheItem {
-                source: source.to_string(),
-                fetched_at: r.get("fetched_at"),
-                payload: r.get("payload"),
-            }))
-        } else {
-            Ok(None)
+    /// Всё в памяти процесса — для тестов без Postgres
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self {
+            iss: Arc::new(memory::MemIssRepo::default()),
+            osdr: Arc::new(memory::MemOsdrRepo::default()),
+            cache: Arc::new(memory::MemCacheRepo::default()),
+            jobs: Arc::new(memory::MemJobRepo::default()),
+            locks: Arc::new(memory::MemJobLocks::default()),
+            tle: Arc::new(memory::MemTleRepo::default()),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,514 @@
+use async_trait::async_trait;
+use sqlx::{postgres::PgRow, PgPool, Row};
+use serde_json::Value;
+use chrono::{DateTime, TimeZone, Utc};
+use crate::domain::{CachePruned, CacheSourceStats, IssFetch, IssPosition, IssRollup, PositionSource, Resolution, RetentionReport, SpaceCacheItem, SpaceCacheVersion, Stats};
+use crate::live::Channel;
+use crate::orbit::tle::Tle;
+use tracing::instrument;
+use super::{CacheRepo, IssRepo, OsdrRepo, TleRepo};
+
+pub struct PgIssRepo(pub PgPool);
+
+#[async_trait]
+impl IssRepo for PgIssRepo {
+    #[instrument(skip_all, level = "debug", name = "repo.log_iss_fetch")]
+    async fn log_fetch(&self, url: &str, p: &IssPosition, payload: Value) -> anyhow::Result<()> {
+        // NOTIFY в том же запросе: уйдёт только вместе с коммитом вставки
+        sqlx::query(
+            "WITH ins AS (
+                 INSERT INTO iss_fetch_log (source_url, payload, lat, lon, altitude_km, velocity_kmh,
+                                            visibility, footprint_km, solar_lat, solar_lon, position_at, source)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                 RETURNING id
+             )
+             SELECT pg_notify($13, json_build_object('id', id, 'position', $14::jsonb)::text) FROM ins"
+        )
+            .bind(url).bind(payload)
+            .bind(p.lat).bind(p.lon).bind(p.altitude_km).bind(p.velocity_kmh)
+            .bind(&p.visibility).bind(p.footprint_km).bind(p.solar_lat).bind(p.solar_lon).bind(p.timestamp)
+            .bind(p.source.as_str())
+            .bind(Channel::IssPosition.as_str()).bind(serde_json::to_value(p)?)
+            .execute(&self.0).await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.get_last_iss")]
+    async fn last(&self) -> anyhow::Result<Option<IssFetch>> {
+        let row_opt = sqlx::query(&format!(
+            "SELECT id, fetched_at, source_url, payload, {POSITION_COLUMNS}
+             FROM iss_fetch_log
+             ORDER BY fetched_at DESC, id DESC LIMIT 1"
+        )).fetch_optional(&self.0).await?;
+
+        Ok(row_opt.map(|row| IssFetch {
+            id: row.get("id"),
+            fetched_at: row.get("fetched_at"),
+            source_url: row.get("source_url"),
+            payload: row.try_get("payload").unwrap_or(serde_json::json!({})),
+            position: position(&row),
+        }))
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.get_iss_trend_data")]
+    async fn trend_data(&self) -> anyhow::Result<Vec<(DateTime<Utc>, IssPosition)>> {
+        let rows = sqlx::query(&format!(
+            "SELECT fetched_at, {POSITION_COLUMNS} FROM iss_fetch_log
+             WHERE lat IS NOT NULL ORDER BY fetched_at DESC, id DESC LIMIT 2"
+        )).fetch_all(&self.0).await?;
+
+        Ok(rows.iter().filter_map(|r| Some((r.get("fetched_at"), position(r)?))).collect())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.iss_track")]
+    async fn track(&self, from: DateTime<Utc>, to: DateTime<Utc>, max_points: usize) -> anyhow::Result<Vec<IssPosition>> {
+        let bucket_secs = (to - from).num_milliseconds() as f64 / 1000.0 / max_points.max(1) as f64;
+        let rows = sqlx::query(&format!(
+            "SELECT DISTINCT ON (bucket) {POSITION_COLUMNS}
+             FROM (
+                 SELECT *, LEAST(floor(extract(epoch FROM position_at - $1) / $3)::bigint, $4) AS bucket
+                 FROM iss_fetch_log
+                 WHERE lat IS NOT NULL AND position_at BETWEEN $1 AND $2
+             ) t
+             ORDER BY bucket, position_at"
+        )).bind(from).bind(to).bind(bucket_secs).bind(max_points as i64 - 1).fetch_all(&self.0).await?;
+
+        Ok(rows.iter().filter_map(position).collect())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.iss_window")]
+    async fn window(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> anyhow::Result<Vec<IssPosition>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {POSITION_COLUMNS} FROM iss_fetch_log
+             WHERE lat IS NOT NULL AND position_at BETWEEN $1 AND $2
+             ORDER BY position_at"
+        )).bind(from).bind(to).fetch_all(&self.0).await?;
+
+        Ok(rows.iter().filter_map(position).collect())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.iss_rollups")]
+    async fn rollups(&self, resolution: Resolution, from: DateTime<Utc>, to: DateTime<Utc>) -> anyhow::Result<Vec<IssRollup>> {
+        let rows = sqlx::query(&format!(
+            "SELECT * FROM {} WHERE bucket > $1::timestamptz - make_interval(secs => $3) AND bucket <= $2 ORDER BY bucket",
+            rollup_table(resolution)?,
+        )).bind(from).bind(to).bind(resolution.bucket_seconds() as f64).fetch_all(&self.0).await?;
+
+        Ok(rows.iter().map(|r| {
+            let stats = |col: &str| Some(Stats {
+                avg: r.get::<Option<f64>, _>(format!("{col}_avg").as_str())?,
+                min: r.get::<Option<f64>, _>(format!("{col}_min").as_str())?,
+                max: r.get::<Option<f64>, _>(format!("{col}_max").as_str())?,
+            });
+            IssRollup {
+                bucket: r.get("bucket"),
+                samples: r.get::<i32, _>("samples") as i64,
+                predicted_samples: r.get::<i32, _>("predicted_samples") as i64,
+                altitude_km: stats("altitude"),
+                velocity_kmh: stats("velocity"),
+                first_at: r.get("first_at"),
+                lat: r.get("first_lat"),
+                lon: r.get("first_lon"),
+            }
+        }).collect())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.iss_ensure_partitions")]
+    async fn ensure_partitions(&self, months_ahead: u32) -> anyhow::Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT iss_fetch_log_ensure_partition(m::date) AS part
+             FROM generate_series(date_trunc('month', now() AT TIME ZONE 'UTC'),
+                                  date_trunc('month', now() AT TIME ZONE 'UTC') + make_interval(months => $1),
+                                  interval '1 month') AS m"
+        ).bind(months_ahead as i32).fetch_all(&self.0).await?;
+
+        Ok(rows.iter().filter_map(|r| r.get::<Option<String>, _>("part")).collect())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.iss_rollup")]
+    async fn rollup(&self) -> anyhow::Result<(u64, u64)> {
+        let mut tx = self.0.begin().await?;
+        // последние часы могли быть неполными, а позиции — прийти с опозданием: пересчитываем с запасом
+        let since: Option<DateTime<Utc>> = sqlx::query_scalar("SELECT max(bucket) - make_interval(hours => $1) FROM iss_rollup_hourly")
+            .bind(ROLLUP_LOOKBACK_HOURS).fetch_one(&mut *tx).await?;
+        let hourly = sqlx::query(
+            "INSERT INTO iss_rollup_hourly AS r (bucket, samples, predicted_samples,
+                 altitude_avg, altitude_min, altitude_max, velocity_avg, velocity_min, velocity_max,
+                 first_at, first_lat, first_lon)
+             SELECT date_trunc('hour', position_at AT TIME ZONE 'UTC') AT TIME ZONE 'UTC',
+                    count(*) FILTER (WHERE source = 'observed'),
+                    count(*) FILTER (WHERE source <> 'observed'),
+                    avg(altitude_km) FILTER (WHERE source = 'observed'),
+                    min(altitude_km) FILTER (WHERE source = 'observed'),
+                    max(altitude_km) FILTER (WHERE source = 'observed'),
+                    avg(velocity_kmh) FILTER (WHERE source = 'observed'),
+                    min(velocity_kmh) FILTER (WHERE source = 'observed'),
+                    max(velocity_kmh) FILTER (WHERE source = 'observed'),
+                    min(position_at),
+                    (array_agg(lat ORDER BY position_at))[1],
+                    (array_agg(lon ORDER BY position_at))[1]
+             FROM iss_fetch_log
+             WHERE lat IS NOT NULL
+               AND ($1::timestamptz IS NULL OR position_at >= $1)
+             GROUP BY 1
+             ON CONFLICT (bucket) DO UPDATE SET
+                 samples = EXCLUDED.samples, predicted_samples = EXCLUDED.predicted_samples,
+                 altitude_avg = EXCLUDED.altitude_avg, altitude_min = EXCLUDED.altitude_min, altitude_max = EXCLUDED.altitude_max,
+                 velocity_avg = EXCLUDED.velocity_avg, velocity_min = EXCLUDED.velocity_min, velocity_max = EXCLUDED.velocity_max,
+                 first_at = EXCLUDED.first_at, first_lat = EXCLUDED.first_lat, first_lon = EXCLUDED.first_lon,
+                 updated_at = now()"
+        ).bind(since).execute(&mut *tx).await?.rows_affected();
+
+        // сутки — из часов; пересчитываются все, которых коснулся пересчёт часов; среднее взвешено по числу измерений
+        let daily = sqlx::query(
+            "INSERT INTO iss_rollup_daily AS d (bucket, samples, predicted_samples,
+                 altitude_avg, altitude_min, altitude_max, velocity_avg, velocity_min, velocity_max,
+                 first_at, first_lat, first_lon)
+             SELECT date_trunc('day', bucket AT TIME ZONE 'UTC') AT TIME ZONE 'UTC',
+                    sum(samples), sum(predicted_samples),
+                    sum(altitude_avg * samples) / NULLIF(sum(samples) FILTER (WHERE altitude_avg IS NOT NULL), 0),
+                    min(altitude_min), max(altitude_max),
+                    sum(velocity_avg * samples) / NULLIF(sum(samples) FILTER (WHERE velocity_avg IS NOT NULL), 0),
+                    min(velocity_min), max(velocity_max),
+                    min(first_at),
+                    (array_agg(first_lat ORDER BY first_at))[1],
+                    (array_agg(first_lon ORDER BY first_at))[1]
+             FROM iss_rollup_hourly
+             WHERE $1::timestamptz IS NULL OR bucket >= date_trunc('day', $1 AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'
+             GROUP BY 1
+             ON CONFLICT (bucket) DO UPDATE SET
+                 samples = EXCLUDED.samples, predicted_samples = EXCLUDED.predicted_samples,
+                 altitude_avg = EXCLUDED.altitude_avg, altitude_min = EXCLUDED.altitude_min, altitude_max = EXCLUDED.altitude_max,
+                 velocity_avg = EXCLUDED.velocity_avg, velocity_min = EXCLUDED.velocity_min, velocity_max = EXCLUDED.velocity_max,
+                 first_at = EXCLUDED.first_at, first_lat = EXCLUDED.first_lat, first_lon = EXCLUDED.first_lon,
+                 updated_at = now()"
+        ).bind(since).execute(&mut *tx).await?.rows_affected();
+        tx.commit().await?;
+
+        Ok((hourly, daily))
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.iss_prune")]
+    async fn prune(&self, raw_before: DateTime<Utc>, hourly_before: DateTime<Utc>) -> anyhow::Result<RetentionReport> {
+        let mut report = RetentionReport::default();
+        // строки, которые следующий rollup ещё пересчитает, удалять нельзя: граница та же, что в `rollup`
+        let covered: Option<DateTime<Utc>> = sqlx::query_scalar("SELECT max(bucket) - make_interval(hours => $1) FROM iss_rollup_hourly")
+            .bind(ROLLUP_LOOKBACK_HOURS).fetch_one(&self.0).await?;
+        if let Some(cutoff) = covered.map(|c| c.min(raw_before)) {
+            let parts: Vec<String> = sqlx::query_scalar(
+                "SELECT c.relname::text FROM pg_inherits i JOIN pg_class c ON c.oid = i.inhrelid
+                 WHERE i.inhparent = 'iss_fetch_log'::regclass ORDER BY 1"
+            ).fetch_all(&self.0).await?;
+            for part in parts {
+                if partition_end(&part).is_some_and(|end| end <= cutoff) {
+                    sqlx::query(&format!("DROP TABLE \"{part}\"")).execute(&self.0).await?;
+                    report.partitions_dropped.push(part);
+                }
+            }
+            report.raw_rows_deleted = sqlx::query("DELETE FROM iss_fetch_log WHERE fetched_at < $1")
+                .bind(cutoff).execute(&self.0).await?.rows_affected();
+        }
+        report.hourly_rows_deleted = sqlx::query("DELETE FROM iss_rollup_hourly WHERE bucket < $1")
+            .bind(hourly_before).execute(&self.0).await?.rows_affected();
+        Ok(report)
+    }
+}
+
+/// Сколько часов до последнего агрегата `rollup` пересчитывает заново. Позиция, опоздавшая
+/// относительно `fetched_at` сильнее, в агрегаты не попадёт — у wheretheiss это секунды
+const ROLLUP_LOOKBACK_HOURS: i32 = 2;
+
+fn rollup_table(resolution: Resolution) -> anyhow::Result<&'static str> {
+    match resolution {
+        Resolution::Hour => Ok("iss_rollup_hourly"),
+        Resolution::Day => Ok("iss_rollup_daily"),
+        Resolution::Raw => anyhow::bail!("raw positions have no rollup table"),
+    }
+}
+
+/// Конец месяца секции `iss_fetch_log_pYYYYMM`; у default-секции и чужих имён — `None`
+fn partition_end(name: &str) -> Option<DateTime<Utc>> {
+    let ym = name.strip_prefix("iss_fetch_log_p").filter(|s| s.len() == 6 && s.bytes().all(|b| b.is_ascii_digit()))?;
+    let (y, m): (i32, u32) = (ym[..4].parse().ok()?, ym[4..].parse().ok()?);
+    let (y, m) = if m == 12 { (y + 1, 1) } else { (y, m + 1) };
+    Utc.with_ymd_and_hms(y, m, 1, 0, 0, 0).single()
+}
+
+const POSITION_COLUMNS: &str =
+    "lat, lon, altitude_km, velocity_kmh, visibility, footprint_km, solar_lat, solar_lon, position_at, source";
+
+/// Позиция из типизированных колонок; `None`, если строку не удалось разобрать при бэкфилле
+fn position(r: &PgRow) -> Option<IssPosition> {
+    Some(IssPosition {
+        lat: r.get::<Option<f64>, _>("lat")?,
+        lon: r.get::<Option<f64>, _>("lon")?,
+        altitude_km: r.get("altitude_km"),
+        velocity_kmh: r.get("velocity_kmh"),
+        visibility: r.get("visibility"),
+        footprint_km: r.get("footprint_km"),
+        solar_lat: r.get("solar_lat"),
+        solar_lon: r.get("solar_lon"),
+        timestamp: r.get::<Option<DateTime<Utc>>, _>("position_at")?,
+        source: PositionSource::parse(r.get("source")),
+    })
+}
+
+pub struct PgOsdrRepo(pub PgPool);
+
+#[async_trait]
+impl OsdrRepo for PgOsdrRepo {
+    #[instrument(skip_all, level = "debug", name = "repo.upsert_osdr_item")]
+    async fn upsert(&self, dataset_id: Option<String>, title: Option<String>, status: Option<String>, updated_at: Option<DateTime<Utc>>, raw: Value) -> anyhow::Result<()> {
+        if let Some(ds) = dataset_id {
+            sqlx::query(&format!(
+                "WITH ins AS (
+                     INSERT INTO osdr_items(dataset_id, title, status, updated_at, raw)
+                     VALUES($1,$2,$3,$4,$5)
+                     ON CONFLICT (dataset_id) DO UPDATE
+                     SET title=EXCLUDED.title, status=EXCLUDED.status,
+                         updated_at=EXCLUDED.updated_at, raw=EXCLUDED.raw
+                     RETURNING id, dataset_id
+                 ) {OSDR_NOTIFY}"
+            )).bind(ds).bind(title).bind(status).bind(updated_at).bind(raw)
+                .bind(Channel::OsdrItem.as_str()).execute(&self.0).await?;
+        } else {
+            sqlx::query(&format!(
+                "WITH ins AS (
+                     INSERT INTO osdr_items(dataset_id, title, status, updated_at, raw)
+                     VALUES($1,$2,$3,$4,$5)
+                     RETURNING id, dataset_id
+                 ) {OSDR_NOTIFY}"
+            )).bind::<Option<String>>(None).bind(title).bind(status).bind(updated_at).bind(raw)
+                .bind(Channel::OsdrItem.as_str()).execute(&self.0).await?;
+        }
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.get_osdr_list")]
+    async fn list(&self, limit: i64) -> anyhow::Result<Vec<Value>> {
+        let rows = sqlx::query(
+            "SELECT id, dataset_id, title, status, updated_at, inserted_at, raw
+             FROM osdr_items
+             ORDER BY inserted_at DESC
+             LIMIT $1"
+        ).bind(limit).fetch_all(&self.0).await?;
+
+        Ok(rows.into_iter().map(|r| {
+            serde_json::json!({
+                "id": r.get::<i64,_>("id"),
+                "dataset_id": r.get::<Option<String>,_>("dataset_id"),
+                "title": r.get::<Option<String>,_>("title"),
+                "status": r.get::<Option<String>,_>("status"),
+                "updated_at": r.get::<Option<DateTime<Utc>>,_>("updated_at"),
+                "inserted_at": r.get::<DateTime<Utc>, _>("inserted_at"),
+                "raw": r.get::<Value,_>("raw"),
+            })
+        }).collect())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.get_osdr_count")]
+    async fn count(&self) -> anyhow::Result<i64> {
+        let row = sqlx::query("SELECT count(*) AS c FROM osdr_items").fetch_one(&self.0).await?;
+        Ok(row.get("c"))
+    }
+}
+
+const OSDR_NOTIFY: &str = "SELECT pg_notify($6, json_build_object('id', id, 'dataset_id', dataset_id)::text) FROM ins";
+
+pub struct PgCacheRepo(pub PgPool);
+
+#[async_trait]
+impl CacheRepo for PgCacheRepo {
+    #[instrument(skip_all, level = "debug", name = "repo.write_space_cache", fields(source = source))]
+    async fn write(&self, source: &str, payload: Value) -> anyhow::Result<bool> {
+        // одним запросом: совпал хеш с последней версией — только last_seen_at и счётчик,
+        // иначе новая строка и NOTIFY; data-modifying CTE выполняются всегда, даже без ссылок на них
+        let inserted = sqlx::query(
+            "WITH h AS (SELECT encode(sha256(convert_to($2::jsonb::text, 'UTF8')), 'hex') AS hash),
+             latest AS (SELECT id, content_hash FROM space_cache WHERE source = $1 ORDER BY id DESC LIMIT 1),
+             seen AS (
+                UPDATE space_cache SET last_seen_at = now()
+                WHERE id = (SELECT id FROM latest) AND content_hash = (SELECT hash FROM h)
+                RETURNING id
+             ),
+             hit AS (
+                INSERT INTO space_cache_stats(source, dedup_hits) SELECT $1, 1 FROM seen
+                ON CONFLICT (source) DO UPDATE SET dedup_hits = space_cache_stats.dedup_hits + 1
+             ),
+             ins AS (
+                INSERT INTO space_cache(source, payload, content_hash)
+                SELECT $1, $2, hash FROM h WHERE NOT EXISTS (SELECT 1 FROM seen)
+                RETURNING id, source, fetched_at
+             )
+             SELECT pg_notify($3, json_build_object('id', id, 'source', source, 'fetched_at', fetched_at)::text) FROM ins"
+        ).bind(source).bind(payload).bind(Channel::SpaceCache.as_str()).fetch_optional(&self.0).await?;
+        Ok(inserted.is_some())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.get_latest_space_cache", fields(source = source))]
+    async fn latest(&self, source: &str) -> anyhow::Result<Option<SpaceCacheItem>> {
+        let row = sqlx::query(
+            "SELECT id, source, fetched_at, last_seen_at, payload FROM space_cache
+             WHERE source = $1 ORDER BY id DESC LIMIT 1"
+        ).bind(source).fetch_optional(&self.0).await?;
+        Ok(row.as_ref().map(cache_item))
+    }
+
+    async fn version(&self, source: &str, id: i64) -> anyhow::Result<Option<SpaceCacheItem>> {
+        let row = sqlx::query(
+            "SELECT id, source, fetched_at, last_seen_at, payload FROM space_cache
+             WHERE source = $1 AND id = $2"
+        ).bind(source).bind(id).fetch_optional(&self.0).await?;
+        Ok(row.as_ref().map(cache_item))
+    }
+
+    async fn previous(&self, source: &str, id: i64) -> anyhow::Result<Option<SpaceCacheItem>> {
+        let row = sqlx::query(
+            "SELECT id, source, fetched_at, last_seen_at, payload FROM space_cache
+             WHERE source = $1 AND id < $2 ORDER BY id DESC LIMIT 1"
+        ).bind(source).bind(id).fetch_optional(&self.0).await?;
+        Ok(row.as_ref().map(cache_item))
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.space_cache_history", fields(source = source))]
+    async fn history(&self, source: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>, limit: i64) -> anyhow::Result<Vec<SpaceCacheVersion>> {
+        let rows = sqlx::query(
+            "SELECT id, fetched_at, last_seen_at, content_hash, pg_column_size(payload)::bigint AS bytes
+             FROM space_cache
+             WHERE source = $1
+               AND ($2::timestamptz IS NULL OR fetched_at >= $2)
+               AND ($3::timestamptz IS NULL OR fetched_at <= $3)
+             ORDER BY id DESC LIMIT $4"
+        ).bind(source).bind(from).bind(to).bind(limit).fetch_all(&self.0).await?;
+
+        Ok(rows.into_iter().map(|r| SpaceCacheVersion {
+            id: r.get("id"),
+            fetched_at: r.get("fetched_at"),
+            last_seen_at: r.get("last_seen_at"),
+            content_hash: r.get("content_hash"),
+            bytes: r.get("bytes"),
+        }).collect())
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.prune_space_cache", fields(source = source))]
+    async fn prune(&self, source: &str, keep_versions: Option<u32>, older_than: Option<DateTime<Utc>>) -> anyhow::Result<CachePruned> {
+        let row = sqlx::query(
+            "WITH ranked AS (
+                SELECT id, fetched_at, row_number() OVER (ORDER BY id DESC) AS rn
+                FROM space_cache WHERE source = $1
+             ),
+             del AS (
+                DELETE FROM space_cache c USING ranked r
+                WHERE c.id = r.id AND r.rn > 1
+                  AND ($2::bigint IS NULL OR r.rn > $2)
+                  AND ($3::timestamptz IS NULL OR r.fetched_at < $3)
+                RETURNING pg_column_size(c.payload) AS bytes
+             ),
+             total AS (SELECT count(*) AS rows, COALESCE(sum(bytes), 0)::bigint AS bytes FROM del),
+             acc AS (
+                INSERT INTO space_cache_stats(source, rows_pruned, bytes_reclaimed, last_pruned_at)
+                SELECT $1, rows, bytes, now() FROM total
+                ON CONFLICT (source) DO UPDATE SET
+                    rows_pruned = space_cache_stats.rows_pruned + EXCLUDED.rows_pruned,
+                    bytes_reclaimed = space_cache_stats.bytes_reclaimed + EXCLUDED.bytes_reclaimed,
+                    last_pruned_at = EXCLUDED.last_pruned_at
+             )
+             SELECT rows, bytes FROM total"
+        ).bind(source).bind(keep_versions.map(i64::from)).bind(older_than).fetch_one(&self.0).await?;
+
+        Ok(CachePruned {
+            source: source.to_string(),
+            rows: row.get::<i64, _>("rows") as u64,
+            bytes: row.get::<i64, _>("bytes") as u64,
+        })
+    }
+
+    async fn stats(&self) -> anyhow::Result<Vec<CacheSourceStats>> {
+        let rows = sqlx::query(
+            "SELECT source, COALESCE(c.versions, 0) AS versions, COALESCE(c.bytes, 0) AS bytes,
+                    c.oldest, c.newest, c.last_seen_at,
+                    COALESCE(s.dedup_hits, 0) AS dedup_hits, COALESCE(s.rows_pruned, 0) AS rows_pruned,
+                    COALESCE(s.bytes_reclaimed, 0) AS bytes_reclaimed, s.last_pruned_at
+             FROM (
+                SELECT source, count(*) AS versions, sum(pg_column_size(payload))::bigint AS bytes,
+                       min(fetched_at) AS oldest, max(fetched_at) AS newest, max(last_seen_at) AS last_seen_at
+                FROM space_cache GROUP BY source
+             ) c
+             FULL JOIN space_cache_stats s USING (source)
+             ORDER BY source"
+        ).fetch_all(&self.0).await?;
+
+        Ok(rows.into_iter().map(|r| CacheSourceStats {
+            source: r.get("source"),
+            versions: r.get("versions"),
+            bytes: r.get("bytes"),
+            oldest: r.get("oldest"),
+            newest: r.get("newest"),
+            last_seen_at: r.get("last_seen_at"),
+            dedup_hits: r.get("dedup_hits"),
+            rows_pruned: r.get("rows_pruned"),
+            bytes_reclaimed: r.get("bytes_reclaimed"),
+            last_pruned_at: r.get("last_pruned_at"),
+        }).collect())
+    }
+}
+
+fn cache_item(r: &PgRow) -> SpaceCacheItem {
+    SpaceCacheItem {
+        id: r.get("id"),
+        source: r.get("source"),
+        fetched_at: r.get("fetched_at"),
+        last_seen_at: r.get("last_seen_at"),
+        payload: r.get("payload"),
+    }
+}
+
+pub struct PgTleRepo(pub PgPool);
+
+#[async_trait]
+impl TleRepo for PgTleRepo {
+    #[instrument(skip_all, level = "debug", name = "repo.save_tle", fields(norad_id = tle.norad_id))]
+    async fn save(&self, tle: &Tle, origin: &str) -> anyhow::Result<bool> {
+        let res = sqlx::query(
+            "INSERT INTO tle_sets(norad_id, epoch, name, line1, line2, origin)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT (norad_id, epoch) DO NOTHING"
+        )
+            .bind(tle.norad_id as i32).bind(tle.epoch).bind(&tle.name)
+            .bind(&tle.line1).bind(&tle.line2).bind(origin)
+            .execute(&self.0).await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[instrument(skip_all, level = "debug", name = "repo.latest_tle", fields(norad_id = norad_id))]
+    async fn latest(&self, norad_id: u32) -> anyhow::Result<Option<Tle>> {
+        let row = sqlx::query(
+            "SELECT name, line1, line2 FROM tle_sets
+             WHERE norad_id = $1 ORDER BY epoch DESC LIMIT 1"
+        ).bind(norad_id as i32).fetch_optional(&self.0).await?;
+
+        match row {
+            Some(r) => Ok(Some(Tle::from_lines(r.get("name"), r.get("line1"), r.get("line2"))?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partition_end_is_next_month() {
+        let month = |y, m| Utc.with_ymd_and_hms(y, m, 1, 0, 0, 0).single();
+        assert_eq!(partition_end("iss_fetch_log_p202412"), month(2025, 1));
+        assert_eq!(partition_end("iss_fetch_log_p202502"), month(2025, 3));
+        // default-секция и чужие имена не удаляются никогда
+        assert_eq!(partition_end("iss_fetch_log_default"), None);
+        assert_eq!(partition_end("iss_fetch_log_p20241"), None);
+        assert_eq!(partition_end("iss_fetch_log_p2024123"), None);
+        assert_eq!(partition_end("iss_fetch_log_p2024ab"), None);
+        assert_eq!(partition_end("iss_fetch_log_p202413"), None);
+        assert_eq!(partition_end("other_p202412"), None);
+    }
+}
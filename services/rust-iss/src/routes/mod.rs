@@ -1,9 +1,11 @@
 use axum::{
-    routing::get,
+    middleware::from_fn,
+    routing::{get, post},
     Router,
 };
 use crate::config::AppState;
 use crate::handlers;
+use crate::middleware::request_id;
 
 pub fn app_router(state: AppState) -> Router {
     Router::new()
@@ -12,12 +14,32 @@ pub fn app_router(state: AppState) -> Router {
         .route("/last", get(handlers::last_iss))
         .route("/fetch", get(handlers::trigger_iss))
         .route("/iss/trend", get(handlers::iss_trend))
+        .route("/iss/track", get(handlers::iss_track))
+        .route("/iss/track.geojson", get(handlers::iss_track_geojson))
+        .route("/iss/track.kml", get(handlers::iss_track_kml))
+        .route("/iss/predict", get(handlers::iss_predict))
+        .route("/iss/passes", get(handlers::iss_passes))
+        .route("/iss/stream", get(handlers::stream::iss_sse))
+        .route("/ws/iss", get(handlers::stream::iss_ws))
+        .route("/events", get(handlers::stream::events_sse))
         // OSDR
         .route("/osdr/sync", get(handlers::osdr_sync))
         .route("/osdr/list", get(handlers::osdr_list))
 
         .route("/space/:src/latest", get(handlers::space_latest))
+        .route("/space/:src/history", get(handlers::space_history))
+        .route("/space/:src/diff", get(handlers::space_diff))
         .route("/space/refresh", get(handlers::space_refresh))
+        .route("/space/refresh/:id", get(handlers::space_refresh_status))
         .route("/space/summary", get(handlers::space_summary))
+        .route("/space/stats", get(handlers::space_stats))
+        .route("/space/sources", get(handlers::space_sources))
+        // Scheduler
+        .route("/jobs", get(handlers::jobs_list))
+        .route("/jobs/:name/run", post(handlers::job_run))
+        .route("/jobs/:name/pause", post(handlers::job_pause))
+        .route("/jobs/:name/resume", post(handlers::job_resume))
+        .fallback(handlers::not_found)
+        .layer(from_fn(request_id))
         .with_state(state)
 }
\ No newline at end of file
@@ -0,0 +1,47 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use crate::config::{AppState, Settings};
+use crate::services::IssService;
+use super::{Job, JobRegistry};
+
+/// Объявляет задачу, которая просто вызывает метод `IssService`
+macro_rules! service_job {
+    ($ty:ident, $name:literal, $call:path) => {
+        pub struct $ty {
+            pub every: Duration,
+        }
+
+        #[async_trait]
+        impl Job for $ty {
+            fn name(&self) -> &'static str { $name }
+            fn interval(&self) -> Duration { self.every }
+            async fn run(&self, st: &AppState) -> anyhow::Result<()> {
+                $call(st).await.map(|_| ())
+            }
+        }
+    };
+}
+
+service_job!(OsdrJob, "osdr", IssService::fetch_and_store_osdr);
+service_job!(IssJob, "iss", IssService::fetch_and_store_iss);
+service_job!(ApodJob, "apod", IssService::fetch_apod);
+service_job!(NeoJob, "neo", IssService::fetch_neo_feed);
+service_job!(DonkiJob, "donki", IssService::fetch_donki);
+service_job!(SpaceXJob, "spacex", IssService::fetch_spacex_next);
+service_job!(TleJob, "tle", IssService::refresh_tle);
+service_job!(IssRollupJob, "iss_rollup", IssService::maintain_iss_log);
+service_job!(SpacePruneJob, "space_prune", IssService::prune_space_cache);
+
+/// Все фоновые задачи сервиса с интервалами из конфигурации
+pub fn default_registry(cfg: &Settings) -> JobRegistry {
+    JobRegistry::new(cfg.job_start_jitter)
+        .register(OsdrJob { every: cfg.interval("osdr") })
+        .register(IssJob { every: cfg.interval("iss") })
+        .register(ApodJob { every: cfg.interval("apod") })
+        .register(NeoJob { every: cfg.interval("neo") })
+        .register(DonkiJob { every: cfg.interval("donki") })
+        .register(SpaceXJob { every: cfg.interval("spacex") })
+        .register(TleJob { every: cfg.interval("tle") })
+        .register(IssRollupJob { every: cfg.interval("iss_rollup") })
+        .register(SpacePruneJob { every: cfg.interval("space_prune") })
+}
@@ -0,0 +1,338 @@
+pub mod jobs;
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+use crate::config::AppState;
+use crate::domain::JobStatus;
+use crate::repositories::{JobLocks, JobRepo};
+use crate::middleware::with_job_trace;
+
+/// Фоновая задача: новый источник = ещё одна реализация + `JobRegistry::register`
+#[async_trait]
+pub trait Job: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn interval(&self) -> Duration;
+    async fn run(&self, st: &AppState) -> anyhow::Result<()>;
+}
+
+/// Результат запуска под блокировкой
+pub enum RunOutcome<T> {
+    Ran(T),
+    /// Задача уже выполняется (в этом процессе или на другой реплике)
+    Skipped,
+}
+
+/// Выполнить задачу `job`, только если удалось взять её блокировку (в Postgres — `pg_try_advisory_lock` по имени).
+/// Фоновые циклы и ручные триггеры (`/fetch`, `/osdr/sync`, `/space/refresh`) идут через одну и ту же блокировку.
+pub async fn run_locked<F, T>(locks: &dyn JobLocks, job: &str, fut: F) -> anyhow::Result<RunOutcome<T>>
+where
+    F: Future<Output = anyhow::Result<T>>,
+{
+    run_locked_wait(locks, job, Duration::ZERO, |_| fut).await
+}
+
+/// Как [`run_locked`], но занятую блокировку ждёт до `max_wait`. В `f` передаётся, пришлось ли ждать:
+/// за это время чужой прогон мог уже сделать ту же работу.
+pub async fn run_locked_wait<F, Fut, T>(locks: &dyn JobLocks, job: &str, max_wait: Duration, f: F) -> anyhow::Result<RunOutcome<T>>
+where
+    F: FnOnce(bool) -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let deadline = tokio::time::Instant::now() + max_wait;
+    let mut waited = false;
+    let lock = loop {
+        if let Some(lock) = locks.try_lock(job).await? {
+            break lock;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            info!(job, "skipped: already running elsewhere");
+            return Ok(RunOutcome::Skipped);
+        }
+        waited = true;
+        tokio::time::sleep(LOCK_POLL).await;
+    };
+
+    let res = f(waited).await;
+    lock.unlock().await;
+    res.map(RunOutcome::Ran)
+}
+
+/// Как часто `run_locked_wait` повторяет попытку взять блокировку
+const LOCK_POLL: Duration = Duration::from_millis(250);
+
+/// Задача в реестре + её состояние в этом процессе
+pub struct JobHandle {
+    job: Box<dyn Job>,
+    paused: AtomicBool,
+    running: AtomicBool,
+}
+
+impl JobHandle {
+    pub fn name(&self) -> &'static str {
+        self.job.name()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub async fn set_paused(&self, jobs: &dyn JobRepo, paused: bool) -> anyhow::Result<()> {
+        jobs.set_paused(self.name(), paused).await?;
+        self.paused.store(paused, Ordering::Relaxed);
+        info!(job = self.name(), paused, "job pause state changed");
+        Ok(())
+    }
+
+    /// Работа задачи, запущенная в обход планировщика (ручной триггер) или им самим: флаг `running`
+    /// и учёт в `job_runs`. Блокировку берёт вызывающий.
+    pub async fn track<F, T>(&self, jobs: &dyn JobRepo, fut: F) -> anyhow::Result<T>
+    where
+        F: Future<Output = anyhow::Result<T>>,
+    {
+        let _running = RunningFlag::set(&self.running);
+        jobs.mark_started(self.name()).await?;
+        let res = fut.await;
+        jobs.mark_finished(self.name(), res.as_ref().err().map(|e| format!("{e:#}"))).await?;
+        res
+    }
+
+    /// Один прогон: advisory lock, учёт в `job_runs`, лог результата
+    pub async fn run(&self, st: &AppState) -> anyhow::Result<RunOutcome<()>> {
+        let name = self.name();
+        let res = run_locked(&*st.repos.locks, name, self.track(&*st.repos.jobs, self.job.run(st))).await;
+        match &res {
+            Ok(RunOutcome::Ran(())) => info!(job = name, "job done"),
+            Ok(RunOutcome::Skipped) => {}
+            Err(e) => error!("{name} err {e:?}"),
+        }
+        res
+    }
+}
+
+/// Флаг `running` на время прогона. Снимает его только тот, кто поставил, —
+/// пропущенный (Skipped) вызов не сбрасывает флаг идущего прогона; при отмене future флаг тоже снимается.
+struct RunningFlag<'a>(Option<&'a AtomicBool>);
+
+impl<'a> RunningFlag<'a> {
+    fn set(flag: &'a AtomicBool) -> Self {
+        let owned = flag.compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed).is_ok();
+        Self(owned.then_some(flag))
+    }
+}
+
+impl Drop for RunningFlag<'_> {
+    fn drop(&mut self) {
+        if let Some(flag) = self.0 {
+            flag.store(false, Ordering::Release);
+        }
+    }
+}
+
+pub struct JobRegistry {
+    jobs: Vec<Arc<JobHandle>>,
+    max_start_jitter: Duration,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl JobRegistry {
+    pub fn new(max_start_jitter: Duration) -> Self {
+        Self { jobs: Vec::new(), max_start_jitter, tasks: Mutex::new(Vec::new()) }
+    }
+
+    pub fn register(mut self, job: impl Job + 'static) -> Self {
+        self.jobs.push(Arc::new(JobHandle {
+            job: Box::new(job),
+            paused: AtomicBool::new(false),
+            running: AtomicBool::new(false),
+        }));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<JobHandle>> {
+        self.jobs.iter().find(|h| h.name() == name).cloned()
+    }
+
+    /// Заводит строки в `job_runs` и поднимает сохранённые флаги паузы
+    pub async fn restore(&self, jobs: &dyn JobRepo) -> anyhow::Result<()> {
+        for h in &self.jobs {
+            let paused = jobs.register(h.name(), h.job.interval().as_secs() as i64).await?;
+            h.paused.store(paused, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Состояние задач из `job_runs` + флаг `running` этого процесса
+    pub async fn status(&self, jobs: &dyn JobRepo) -> anyhow::Result<Vec<JobStatus>> {
+        let mut rows = jobs.list().await?;
+        rows.retain(|r| self.get(&r.name).is_some());
+        for r in rows.iter_mut() {
+            if let Some(h) = self.get(&r.name) {
+                r.running = h.running.load(Ordering::Relaxed);
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Запускает все задачи: первый прогон через случайную задержку, дальше — по сетке настенных часов.
+    /// После отмены `cancel` новые прогоны не начинаются, текущий доходит до конца.
+    pub fn spawn_all(&self, st: &AppState, cancel: CancellationToken) {
+        let mut tasks = self.tasks.lock().unwrap();
+        for h in &self.jobs {
+            let h = h.clone();
+            let st = st.clone();
+            let cancel = cancel.clone();
+            let interval = h.job.interval();
+            let jitter = random_upto(self.max_start_jitter.min(interval));
+            tasks.push(tokio::spawn(async move {
+                info!(job = h.name(), interval_s = interval.as_secs(), jitter_ms = jitter.as_millis() as u64, "job scheduled");
+                let first = Utc::now() + chrono::Duration::from_std(jitter).unwrap_or_default();
+                if let Err(e) = st.repos.jobs.set_next_run(h.name(), first).await { warn!(job = h.name(), "job_runs update failed: {e}") }
+                let mut wait = jitter;
+                // смещение сетки = стартовый jitter, чтобы задачи с одинаковым интервалом не совпадали
+                let offset = jitter;
+                loop {
+                    tokio::select! {
+                        _ = cancel.cancelled() => break,
+                        _ = tokio::time::sleep(wait) => {}
+                    }
+                    if h.is_paused() {
+                        info!(job = h.name(), "skipped: paused");
+                    } else {
+                        with_job_trace(h.name(), async { let _ = h.run(&st).await; }).await;
+                    }
+                    let now = Utc::now();
+                    let next = next_aligned(now, interval, offset);
+                    if let Err(e) = st.repos.jobs.set_next_run(h.name(), next).await { warn!(job = h.name(), "job_runs update failed: {e}") }
+                    wait = (next - now).to_std().unwrap_or(Duration::ZERO);
+                }
+                info!(job = h.name(), "job loop stopped");
+            }));
+        }
+    }
+
+    /// Ждёт завершения циклов (и идущих прогонов) после отмены; по таймауту обрывает оставшиеся
+    pub async fn drain(&self, timeout: Duration) {
+        let tasks: Vec<_> = std::mem::take(&mut *self.tasks.lock().unwrap());
+        let aborts: Vec<_> = tasks.iter().map(|t| t.abort_handle()).collect();
+        if tokio::time::timeout(timeout, join_all(tasks)).await.is_err() {
+            let running: Vec<_> = self.jobs.iter().filter(|h| h.running.load(Ordering::Relaxed)).map(|h| h.name()).collect();
+            warn!(?running, "jobs did not finish in {}s, aborting", timeout.as_secs());
+            aborts.iter().for_each(|a| a.abort());
+        }
+    }
+}
+
+async fn join_all(tasks: Vec<JoinHandle<()>>) {
+    for t in tasks {
+        let _ = t.await;
+    }
+}
+
+/// Ближайший момент после `now` вида `epoch + offset + k * interval`
+fn next_aligned(now: DateTime<Utc>, interval: Duration, offset: Duration) -> DateTime<Utc> {
+    let period = (interval.as_millis() as i64).max(1);
+    let off = offset.as_millis() as i64 % period;
+    let k = (now.timestamp_millis() - off).div_euclid(period) + 1;
+    DateTime::from_timestamp_millis(k * period + off).unwrap_or(now)
+}
+
+fn random_upto(max: Duration) -> Duration {
+    let ms = max.as_millis() as u64;
+    Duration::from_millis(if ms == 0 { 0 } else { rand::thread_rng().gen_range(0..ms) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Reply};
+
+    #[test]
+    fn running_flag_is_cleared_only_by_its_owner() {
+        let flag = AtomicBool::new(false);
+        let owner = RunningFlag::set(&flag);
+        assert!(flag.load(Ordering::Relaxed));
+        // второй вызов флаг не ставил — и снимать его не должен
+        drop(RunningFlag::set(&flag));
+        assert!(flag.load(Ordering::Relaxed));
+        drop(owner);
+        assert!(!flag.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn run_records_job_runs_and_skips_when_locked() {
+        let (url, hits) = testing::upstream(vec![Reply::json(serde_json::json!({ "latitude": 1.0, "longitude": 2.0 }))]).await;
+        let st = AppState::in_memory(&[("WHERE_ISS_URL", &url)]).await;
+        let job = st.jobs.get("iss").unwrap();
+        let runs = |st: &AppState| {
+            let st = st.clone();
+            async move { st.jobs.status(&*st.repos.jobs).await.unwrap().into_iter().find(|j| j.name == "iss").unwrap() }
+        };
+
+        assert!(matches!(job.run(&st).await.unwrap(), RunOutcome::Ran(())));
+        let after = runs(&st).await;
+        assert_eq!((after.run_count, after.running, after.last_error), (1, false, None));
+        assert!(after.last_finished.is_some());
+
+        // блокировку держит «другая реплика»: прогон пропускается и в job_runs не попадает
+        let lock = st.repos.locks.try_lock("iss").await.unwrap().unwrap();
+        assert!(matches!(job.run(&st).await.unwrap(), RunOutcome::Skipped));
+        assert_eq!(runs(&st).await.run_count, 1);
+        lock.unlock().await;
+        assert!(matches!(job.run(&st).await.unwrap(), RunOutcome::Ran(())));
+        assert_eq!(runs(&st).await.run_count, 2);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_run_is_recorded_and_releases_lock() {
+        let (url, _) = testing::upstream(vec![Reply::status(404)]).await;
+        let st = AppState::in_memory(&[("WHERE_ISS_URL", &url)]).await;
+        assert!(st.jobs.get("iss").unwrap().run(&st).await.is_err());
+        let job = st.jobs.status(&*st.repos.jobs).await.unwrap().into_iter().find(|j| j.name == "iss").unwrap();
+        assert!(job.last_error.is_some_and(|e| e.contains("404")));
+        assert!(st.repos.locks.try_lock("iss").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn lock_wait_reports_waiting() {
+        let st = AppState::in_memory(&[]).await;
+        let locks = st.repos.locks.clone();
+        let lock = locks.try_lock("iss").await.unwrap().unwrap();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            lock.unlock().await;
+        });
+        let out = run_locked_wait(&*locks, "iss", Duration::from_secs(5), |waited| async move { Ok(waited) }).await.unwrap();
+        assert!(matches!(out, RunOutcome::Ran(true)));
+        // блокировка освобождена после прогона
+        assert!(locks.try_lock("iss").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn pause_is_persisted() {
+        let st = AppState::in_memory(&[]).await;
+        st.jobs.get("iss").unwrap().set_paused(&*st.repos.jobs, true).await.unwrap();
+        assert!(st.jobs.get("iss").unwrap().is_paused());
+        // новый реестр поднимает флаг из job_runs
+        let registry = crate::scheduler::jobs::default_registry(&st.settings);
+        registry.restore(&*st.repos.jobs).await.unwrap();
+        assert!(registry.get("iss").unwrap().is_paused());
+        assert!(!registry.get("osdr").unwrap().is_paused());
+    }
+
+    #[test]
+    fn next_aligned_keeps_grid_offset() {
+        let now = DateTime::from_timestamp(1_000_000_007, 0).unwrap();
+        let next = next_aligned(now, Duration::from_secs(60), Duration::from_secs(5));
+        assert_eq!(next.timestamp() % 60, 5);
+        assert!(next > now && next - now <= chrono::Duration::seconds(60));
+    }
+}
@@ -1,26 +1,88 @@
+pub mod refresh;
+
 use crate::config::AppState;
-use crate::repositories::IssRepository;
+use crate::domain::{CachePruned, IssPosition, RetentionReport, SpaceSource};
+use crate::orbit::{self, tle::Tle, Propagator, ISS_NORAD_ID};
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use anyhow::Context;
 use serde_json::Value;
-use std::time::Duration;
+use tracing::{debug, info, instrument, warn};
 
 pub struct IssService;
 
+/// Сколько месяцев вперёд держать готовые секции `iss_fetch_log`
+const PARTITIONS_AHEAD: u32 = 1;
+
 impl IssService {
-    pub async fn fetch_and_store_iss(pool: &sqlx::PgPool, url: &str) -> anyhow::Result<()> {
-        let client = reqwest::Client::builder().timeout(Duration::from_secs(20)).build()?;
-        let resp = client.get(url).send().await?;
-        let json: Value = resp.json().await?;
-        IssRepository::log_iss_fetch(pool, url, json).await
+    /// Если wheretheiss.at недоступен, пишем позицию по SGP4 (`source = predicted`), но прогон всё равно считается ошибкой
+    #[instrument(skip_all, level = "info", name = "svc.fetch_and_store_iss")]
+    pub async fn fetch_and_store_iss(st: &AppState) -> anyhow::Result<()> {
+        let observed = async {
+            let json = st.clients.where_iss.position().await?;
+            let position = IssPosition::from_wheretheiss(&json, Utc::now())?;
+            anyhow::Ok((position, json))
+        }.await;
+        match observed {
+            Ok((position, json)) => st.repos.iss.log_fetch(st.clients.where_iss.url(), &position, json).await,
+            Err(e) => match Self::store_predicted(st).await {
+                Ok(()) => Err(e.context("wheretheiss unavailable, stored predicted position")),
+                Err(pe) => {
+                    warn!("no predicted position either: {pe:#}");
+                    Err(e)
+                }
+            },
+        }
     }
 
-    pub async fn fetch_and_store_osdr(st: &AppState) -> anyhow::Result<usize> {
-        let client = reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?;
-        let resp = client.get(&st.nasa_url).send().await?;
-        if !resp.status().is_success() {
-            anyhow::bail!("OSDR request status {}", resp.status());
+    async fn store_predicted(st: &AppState) -> anyhow::Result<()> {
+        let tle = st.repos.tle.latest(ISS_NORAD_ID).await?
+            .ok_or_else(|| anyhow::anyhow!("no TLE loaded"))?;
+        let now = Utc::now();
+        orbit::check_tle_age(&tle, now, st.settings.tle_max_age).map_err(anyhow::Error::msg)?;
+        let position = Propagator::new(tle.clone())?.position_at(now)?;
+        let origin = format!("sgp4:{}@{}", tle.norad_id, tle.epoch.to_rfc3339());
+        st.repos.iss.log_fetch(&origin, &position, serde_json::json!({ "tle": [tle.line1, tle.line2] })).await
+    }
+
+    /// Свежий TLE МКС из файла (`TLE_FILE`) или по `TLE_URL`; одинаковые эпохи не дублируются
+    #[instrument(skip_all, level = "info", name = "svc.refresh_tle")]
+    pub async fn refresh_tle(st: &AppState) -> anyhow::Result<()> {
+        let (text, origin) = match &st.settings.tle_file {
+            Some(path) => (tokio::fs::read_to_string(path).await?, path.clone()),
+            None => (st.clients.tle.fetch().await?, st.settings.tle_url.clone()),
+        };
+        let tle = Tle::parse_first(&text, Some(ISS_NORAD_ID))?;
+        // набор, на котором SGP4 не инициализируется, не сохраняем
+        Propagator::new(tle.clone())?;
+        if st.repos.tle.save(&tle, &origin).await? {
+            info!(epoch = %tle.epoch, origin, "new TLE stored");
         }
-        let json: Value = resp.json().await?;
+        Ok(())
+    }
+
+    /// Секции впрок, свёртка в агрегаты, затем удаление по срокам хранения — именно в таком порядке
+    #[instrument(skip_all, level = "info", name = "svc.maintain_iss_log")]
+    pub async fn maintain_iss_log(st: &AppState) -> anyhow::Result<RetentionReport> {
+        let partitions_created = st.repos.iss.ensure_partitions(PARTITIONS_AHEAD).await?;
+        let (hourly_buckets, daily_buckets) = st.repos.iss.rollup().await?;
+        let now = Utc::now();
+        let pruned = st.repos.iss.prune(
+            now - chrono::Duration::from_std(st.settings.iss_raw_retention)?,
+            now - chrono::Duration::from_std(st.settings.iss_hourly_retention)?,
+        ).await?;
+        let report = RetentionReport { partitions_created, hourly_buckets, daily_buckets, ..pruned };
+        info!(
+            created = ?report.partitions_created, dropped = ?report.partitions_dropped,
+            hourly = report.hourly_buckets, daily = report.daily_buckets,
+            raw_deleted = report.raw_rows_deleted, hourly_deleted = report.hourly_rows_deleted,
+            "iss_fetch_log maintained"
+        );
+        Ok(report)
+    }
+
+    #[instrument(skip_all, level = "info", name = "svc.fetch_and_store_osdr")]
+    pub async fn fetch_and_store_osdr(st: &AppState) -> anyhow::Result<usize> {
+        let json = st.clients.osdr.datasets().await?;
         let items = if let Some(a) = json.as_array() { a.clone() }
             else if let Some(v) = json.get("items").and_then(|x| x.as_array()) { v.clone() }
             else if let Some(v) = json.get("results").and_then(|x| x.as_array()) { v.clone() }
@@ -33,66 +95,93 @@ impl IssService {
             let status = Self::s_pick(&item, &["status","state","lifecycle"]);
             let updated = Self::t_pick(&item, &["updated","updated_at","modified","lastUpdated","timestamp"]);
             
-            IssRepository::upsert_osdr_item(&st.pool, id, title, status, updated, item).await?;
+            st.repos.osdr.upsert(id, title, status, updated, item).await?;
             written += 1;
         }
         Ok(written)
     }
 
+    #[instrument(skip_all, level = "info", name = "svc.fetch_apod")]
     pub async fn fetch_apod(st: &AppState) -> anyhow::Result<()> {
-        let url = "https://api.nasa.gov/planetary/apod";
-        let client = reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?;
-        let mut req = client.get(url).query(&[("thumbs","true")]);
-        if !st.nasa_key.is_empty() { req = req.query(&[("api_key",&st.nasa_key)]); }
-        let json: Value = req.send().await?.json().await?;
-        IssRepository::write_space_cache(&st.pool, "apod", json).await
+        let json = st.clients.nasa.apod().await?;
+        Self::store_snapshot(st, SpaceSource::Apod, json).await
     }
 
+    #[instrument(skip_all, level = "info", name = "svc.fetch_neo_feed")]
     pub async fn fetch_neo_feed(st: &AppState) -> anyhow::Result<()> {
-        let today = Utc::now().date_naive();
-        let start = today - chrono::Days::new(2);
-        let url = "https://api.nasa.gov/neo/rest/v1/feed";
-        let client = reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?;
-        let mut req = client.get(url).query(&[
-            ("start_date", start.to_string()),
-            ("end_date", today.to_string()),
-        ]);
-        if !st.nasa_key.is_empty() { req = req.query(&[("api_key",&st.nasa_key)]); }
-        let json: Value = req.send().await?.json().await?;
-        IssRepository::write_space_cache(&st.pool, "neo", json).await
+        let (start, today) = Self::last_days(2);
+        let json = st.clients.nasa.neo_feed(&start, &today).await?;
+        Self::store_snapshot(st, SpaceSource::Neo, json).await
     }
 
+    #[instrument(skip_all, level = "info", name = "svc.fetch_donki")]
     pub async fn fetch_donki(st: &AppState) -> anyhow::Result<()> {
-        let _ = Self::fetch_donki_flr(st).await;
-        let _ = Self::fetch_donki_cme(st).await;
-        Ok(())
+        // обе ленты пробуем в любом случае, но сбой любой из них — сбой задачи
+        let flr = Self::fetch_donki_flr(st).await;
+        let cme = Self::fetch_donki_cme(st).await;
+        flr.context("DONKI FLR")?;
+        cme.context("DONKI CME")
+    }
+
+    /// Обновление одного источника: для `flr`/`cme` — только своя лента DONKI
+    pub async fn fetch_space(st: &AppState, src: SpaceSource) -> anyhow::Result<()> {
+        match src {
+            SpaceSource::Apod => Self::fetch_apod(st).await,
+            SpaceSource::Neo => Self::fetch_neo_feed(st).await,
+            SpaceSource::Flr => Self::fetch_donki_flr(st).await,
+            SpaceSource::Cme => Self::fetch_donki_cme(st).await,
+            SpaceSource::Spacex => Self::fetch_spacex_next(st).await,
+        }
     }
 
+    #[instrument(skip_all, level = "info", name = "svc.fetch_donki_flr")]
     async fn fetch_donki_flr(st: &AppState) -> anyhow::Result<()> {
         let (from,to) = Self::last_days(5);
-        let url = "https://api.nasa.gov/DONKI/FLR";
-        let client = reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?;
-        let mut req = client.get(url).query(&[("startDate",from),("endDate",to)]);
-        if !st.nasa_key.is_empty() { req = req.query(&[("api_key",&st.nasa_key)]); }
-        let json: Value = req.send().await?.json().await?;
-        IssRepository::write_space_cache(&st.pool, "flr", json).await
+        let json = st.clients.nasa.donki("FLR", &from, &to).await?;
+        Self::store_snapshot(st, SpaceSource::Flr, json).await
     }
 
+    #[instrument(skip_all, level = "info", name = "svc.fetch_donki_cme")]
     async fn fetch_donki_cme(st: &AppState) -> anyhow::Result<()> {
         let (from,to) = Self::last_days(5);
-        let url = "https://api.nasa.gov/DONKI/CME";
-        let client = reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?;
-        let mut req = client.get(url).query(&[("startDate",from),("endDate",to)]);
-        if !st.nasa_key.is_empty() { req = req.query(&[("api_key",&st.nasa_key)]); }
-        let json: Value = req.send().await?.json().await?;
-        IssRepository::write_space_cache(&st.pool, "cme", json).await
+        let json = st.clients.nasa.donki("CME", &from, &to).await?;
+        Self::store_snapshot(st, SpaceSource::Cme, json).await
     }
 
+    #[instrument(skip_all, level = "info", name = "svc.fetch_spacex_next")]
     pub async fn fetch_spacex_next(st: &AppState) -> anyhow::Result<()> {
-        let url = "https://api.spacexdata.com/v4/launches/next";
-        let client = reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?;
-        let json: Value = client.get(url).send().await?.json().await?;
-        IssRepository::write_space_cache(&st.pool, "spacex", json).await
+        let json = st.clients.spacex.next_launch().await?;
+        Self::store_snapshot(st, SpaceSource::Spacex, json).await
+    }
+
+    /// Неизменившийся снимок новой версии не создаёт
+    async fn store_snapshot(st: &AppState, source: SpaceSource, json: Value) -> anyhow::Result<()> {
+        if !st.repos.cache.write(source.as_str(), json).await? {
+            debug!(source = source.as_str(), "space_cache: payload unchanged, bumped last_seen_at");
+        }
+        Ok(())
+    }
+
+    /// Чистит версии `space_cache` по политике каждого источника; источник без пределов пропускается
+    #[instrument(skip_all, level = "info", name = "svc.prune_space_cache")]
+    pub async fn prune_space_cache(st: &AppState) -> anyhow::Result<Vec<CachePruned>> {
+        let now = Utc::now();
+        let mut report = Vec::new();
+        for src in SpaceSource::ALL {
+            let policy = st.settings.space_retention_for(src);
+            if policy.is_off() {
+                continue;
+            }
+            let keep_versions = (policy.versions > 0).then_some(policy.versions);
+            let older_than = match policy.max_age.is_zero() {
+                true => None,
+                false => Some(now - chrono::Duration::from_std(policy.max_age)?),
+            };
+            let pruned = st.repos.cache.prune(src.as_str(), keep_versions, older_than).await?;
+            info!(source = src.as_str(), rows = pruned.rows, bytes = pruned.bytes, "space_cache pruned");
+            report.push(pruned);
+        }
+        Ok(report)
     }
 
     fn last_days(n: i64) -> (String,String) {
@@ -126,4 +215,112 @@ impl IssService {
         }
         None
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::PositionSource;
+    use crate::orbit::tle::Tle;
+    use crate::testing::{self, Reply, ISS_TLE};
+
+    #[tokio::test]
+    async fn stores_typed_iss_position() {
+        let body = serde_json::json!({ "latitude": 10.5, "longitude": "-20.25", "altitude": 420.1, "velocity": 27600.0, "timestamp": 1_700_000_000 });
+        let (url, _) = testing::upstream(vec![Reply::json(body.clone())]).await;
+        let st = AppState::in_memory(&[("WHERE_ISS_URL", &url)]).await;
+
+        IssService::fetch_and_store_iss(&st).await.unwrap();
+        let last = st.repos.iss.last().await.unwrap().unwrap();
+        let pos = last.position.unwrap();
+        assert_eq!((pos.lat, pos.lon, pos.altitude_km), (10.5, -20.25, Some(420.1)));
+        assert_eq!(pos.timestamp.timestamp(), 1_700_000_000);
+        assert_eq!((last.source_url, last.payload), (url, body));
+    }
+
+    #[tokio::test]
+    async fn rejects_payload_without_coordinates() {
+        let (url, _) = testing::upstream(vec![Reply::json(serde_json::json!({ "message": "maintenance" }))]).await;
+        let st = AppState::in_memory(&[("WHERE_ISS_URL", &url)]).await;
+        assert!(IssService::fetch_and_store_iss(&st).await.is_err());
+        assert!(st.repos.iss.last().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_predicted_position() {
+        let (url, hits) = testing::upstream(vec![Reply::status(503)]).await;
+        let st = AppState::in_memory(&[("WHERE_ISS_URL", &url), ("RETRY_ISS_MAX_ATTEMPTS", "1"), ("TLE_MAX_AGE_SECONDS", "2000000000")]).await;
+
+        // без TLE подставить нечего — исходная ошибка апстрима
+        assert!(IssService::fetch_and_store_iss(&st).await.is_err());
+        assert!(st.repos.iss.last().await.unwrap().is_none());
+
+        st.repos.tle.save(&Tle::from_lines(None, ISS_TLE[0], ISS_TLE[1]).unwrap(), "test").await.unwrap();
+        let err = IssService::fetch_and_store_iss(&st).await.unwrap_err();
+        assert!(format!("{err:#}").contains("stored predicted position"), "{err:#}");
+        let pos = st.repos.iss.last().await.unwrap().unwrap().position.unwrap();
+        assert_eq!(pos.source, PositionSource::Predicted);
+        assert!(pos.altitude_km.is_some_and(|a| (300.0..450.0).contains(&a)));
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn osdr_sync_upserts_by_dataset_id() {
+        let items = serde_json::json!({ "items": [
+            { "dataset_id": "OSD-1", "title": "Rodent Research", "updated_at": "2024-05-01T00:00:00Z" },
+            { "id": 42, "name": "Plant Habitat", "timestamp": 1_700_000_000 },
+        ]});
+        let (url, _) = testing::upstream(vec![Reply::json(items)]).await;
+        let st = AppState::in_memory(&[("NASA_API_URL", &url)]).await;
+
+        assert_eq!(IssService::fetch_and_store_osdr(&st).await.unwrap(), 2);
+        // повторная синхронизация обновляет строки, а не дублирует их
+        assert_eq!(IssService::fetch_and_store_osdr(&st).await.unwrap(), 2);
+        assert_eq!(st.repos.osdr.count().await.unwrap(), 2);
+        let list = st.repos.osdr.list(10).await.unwrap();
+        let plant = list.iter().find(|r| r["dataset_id"] == "42").unwrap();
+        assert_eq!(plant["title"], "Plant Habitat");
+        assert_eq!(plant["updated_at"], "2023-11-14T22:13:20Z");
+    }
+
+    #[tokio::test]
+    async fn unchanged_snapshot_does_not_create_version() {
+        let launch = serde_json::json!({ "name": "Crew-12", "date_utc": "2026-11-01T00:00:00Z" });
+        let (url, _) = testing::upstream(vec![
+            Reply::json(launch.clone()),
+            Reply::json(launch),
+            Reply::json(serde_json::json!({ "name": "Crew-12", "date_utc": "2026-11-02T00:00:00Z" })),
+        ]).await;
+        let st = AppState::in_memory(&[("SPACEX_API_URL", &url)]).await;
+
+        for _ in 0..3 {
+            IssService::fetch_spacex_next(&st).await.unwrap();
+        }
+        let stats = st.repos.cache.stats().await.unwrap();
+        assert_eq!((stats[0].source.as_str(), stats[0].versions, stats[0].dedup_hits), ("spacex", 2, 1));
+        let latest = st.repos.cache.latest("spacex").await.unwrap().unwrap();
+        assert_eq!(latest.payload["date_utc"], "2026-11-02T00:00:00Z");
+    }
+
+    #[tokio::test]
+    async fn prunes_by_source_policy() {
+        let st = AppState::in_memory(&[
+            ("SPACE_RETENTION_VERSIONS", "0"),
+            ("SPACE_RETENTION_MAX_AGE_SECONDS", "0"),
+            ("SPACE_RETENTION_APOD_VERSIONS", "2"),
+        ]).await;
+        for i in 0..5 {
+            st.repos.cache.write("apod", serde_json::json!({ "n": i })).await.unwrap();
+            st.repos.cache.write("neo", serde_json::json!({ "n": i })).await.unwrap();
+        }
+
+        let report = IssService::prune_space_cache(&st).await.unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!((report[0].source.as_str(), report[0].rows), ("apod", 3));
+        assert_eq!(st.repos.cache.latest("apod").await.unwrap().unwrap().payload["n"], 4);
+        let stats = st.repos.cache.stats().await.unwrap();
+        let versions = |src: &str| stats.iter().find(|s| s.source == src).map(|s| (s.versions, s.rows_pruned));
+        assert_eq!(versions("apod"), Some((2, 3)));
+        assert_eq!(versions("neo"), Some((5, 0)));
+    }
+}
@@ -0,0 +1,222 @@
+//! Ручное обновление снимков (`/space/refresh`). Каждый источник обновляется в своей задаче tokio,
+//! не больше [`CONCURRENCY`] одновременно; запрос на источник, который уже обновляется в этом процессе,
+//! присоединяется к идущему прогону, а не запускает второй.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use futures_util::future::{join_all, BoxFuture, FutureExt, Shared};
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+use tracing::info;
+use crate::config::AppState;
+use crate::domain::{RefreshStatus, SourceRefresh, SpaceSource};
+use crate::errors::ApiError;
+use crate::middleware::spawn_in_request;
+use crate::scheduler::{run_locked_wait, RunOutcome};
+use super::IssService;
+
+/// Одновременных обновлений на процесс: у четырёх источников из пяти общая квота api.nasa.gov
+const CONCURRENCY: usize = 3;
+/// Сколько ждать блокировку задачи, которую уже выполняет планировщик или другая реплика
+const LOCK_WAIT: Duration = Duration::from_secs(60);
+/// Сколько последних прогонов помнить для `GET /space/refresh/:id`
+const RUNS_KEPT: usize = 64;
+
+type Pending = Shared<BoxFuture<'static, SourceRefresh>>;
+
+pub struct Refresher {
+    permits: Arc<Semaphore>,
+    inflight: Mutex<HashMap<SpaceSource, (Instant, Pending)>>,
+    runs: Mutex<VecDeque<Arc<RefreshRun>>>,
+}
+
+impl Default for Refresher {
+    fn default() -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(CONCURRENCY)),
+            inflight: Mutex::default(),
+            runs: Mutex::default(),
+        }
+    }
+}
+
+impl Refresher {
+    /// Запускает обновление `sources` (или присоединяется к идущему) и сразу возвращает прогон
+    pub fn start(&self, st: &AppState, sources: &[SpaceSource]) -> Arc<RefreshRun> {
+        let parts = sources.iter().map(|&src| self.part(st, src)).collect();
+        let run = Arc::new(RefreshRun { id: uuid::Uuid::new_v4().simple().to_string(), started_at: Utc::now(), parts });
+        let mut runs = self.runs.lock().unwrap();
+        if runs.len() >= RUNS_KEPT {
+            runs.pop_front();
+        }
+        runs.push_back(run.clone());
+        run
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<RefreshRun>> {
+        self.runs.lock().unwrap().iter().find(|r| r.id == id).cloned()
+    }
+
+    fn part(&self, st: &AppState, source: SpaceSource) -> Part {
+        let mut inflight = self.inflight.lock().unwrap();
+        if let Some((started, fut)) = inflight.get(&source) {
+            // now_or_never опрашивает задачу: готовая значит, что прогон закончился и нужен новый
+            if fut.clone().now_or_never().is_none() {
+                return Part { source, joined: true, started: *started, fut: fut.clone() };
+            }
+        }
+        let permits = self.permits.clone();
+        let st = st.clone();
+        let fut = spawn_in_request(async move {
+            let _permit = permits.acquire_owned().await;
+            refresh_one(&st, source).await
+        })
+        .map(move |res| res.unwrap_or_else(|e| SourceRefresh {
+            error_code: Some("INTERNAL".into()),
+            error: Some(format!("refresh task failed: {e}")),
+            ..report(source, 0)
+        }))
+        .boxed()
+        .shared();
+        let started = Instant::now();
+        inflight.insert(source, (started, fut.clone()));
+        Part { source, joined: false, started, fut }
+    }
+}
+
+/// Один вызов `/space/refresh`: источники и их задачи
+pub struct RefreshRun {
+    id: String,
+    started_at: DateTime<Utc>,
+    parts: Vec<Part>,
+}
+
+struct Part {
+    source: SpaceSource,
+    /// Задача начата раньше этого запроса
+    joined: bool,
+    started: Instant,
+    fut: Pending,
+}
+
+impl RefreshRun {
+    /// Ждёт все источники не дольше `max_wait`; не успевшие остаются `pending` и доделываются в фоне
+    pub async fn wait(&self, max_wait: Duration) -> RefreshStatus {
+        let all = join_all(self.parts.iter().map(|p| p.fut.clone()));
+        let _ = tokio::time::timeout(max_wait, all).await;
+        self.status()
+    }
+
+    pub fn status(&self) -> RefreshStatus {
+        let sources: Vec<SourceRefresh> = self.parts.iter().map(|p| match p.fut.clone().now_or_never() {
+            Some(r) => SourceRefresh { joined: r.joined || p.joined, ..r },
+            None => SourceRefresh { pending: true, joined: p.joined, ..report(p.source, p.started.elapsed().as_millis() as u64) },
+        }).collect();
+        let done = sources.iter().all(|s| !s.pending);
+        RefreshStatus {
+            id: self.id.clone(),
+            started_at: self.started_at,
+            status: if done { "done" } else { "running" },
+            ok: done && sources.iter().all(|s| s.ok),
+            refreshed: sources.iter().filter(|s| s.ok).map(|s| s.source).collect(),
+            sources,
+        }
+    }
+}
+
+fn report(source: SpaceSource, duration_ms: u64) -> SourceRefresh {
+    SourceRefresh { source, ok: false, pending: false, joined: false, duration_ms, error_code: None, error: None, fetched_at: None, last_seen_at: None }
+}
+
+/// Обновление под блокировкой задачи источника. Если её держал чужой прогон и он успел обновить
+/// источник после нашего запроса, повторно апстрим не дёргаем.
+async fn refresh_one(st: &AppState, source: SpaceSource) -> SourceRefresh {
+    let asked = Utc::now();
+    let t0 = Instant::now();
+    let outcome = run_locked_wait(&*st.repos.locks, source.job(), LOCK_WAIT, |waited| async move {
+        if waited && refreshed_since(st, source, asked).await? {
+            return Ok(true);
+        }
+        // ручной прогон виден в `job_runs` (и `/space/sources`) так же, как плановый
+        let fetch = IssService::fetch_space(st, source);
+        match st.jobs.get(source.job()) {
+            Some(job) => job.track(&*st.repos.jobs, fetch).await,
+            None => fetch.await,
+        }.map(|()| false)
+    }).await;
+
+    let mut r = report(source, t0.elapsed().as_millis() as u64);
+    match outcome {
+        Ok(RunOutcome::Ran(joined)) => (r.ok, r.joined) = (true, joined),
+        Ok(RunOutcome::Skipped) => {
+            let e = ApiError::JobRunning(source.job().into());
+            (r.error_code, r.error) = (Some(e.code()), Some(e.public_message()));
+        }
+        Err(e) => {
+            let e = ApiError::from(e);
+            (r.error_code, r.error) = (Some(e.code()), Some(e.public_message()));
+        }
+    }
+    if let Ok(Some(latest)) = st.repos.cache.latest(source.as_str()).await {
+        (r.fetched_at, r.last_seen_at) = (Some(latest.fetched_at), Some(latest.last_seen_at));
+    }
+    info!(source = source.as_str(), ok = r.ok, joined = r.joined, duration_ms = r.duration_ms, "space refresh");
+    r
+}
+
+async fn refreshed_since(st: &AppState, source: SpaceSource, at: DateTime<Utc>) -> anyhow::Result<bool> {
+    Ok(st.repos.cache.latest(source.as_str()).await?.is_some_and(|i| i.last_seen_at >= at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Reply};
+
+    #[tokio::test]
+    async fn refresh_reports_and_records_job_run() {
+        let (url, hits) = testing::upstream(vec![Reply::json(serde_json::json!({ "name": "Crew-12" }))]).await;
+        let st = AppState::in_memory(&[("SPACEX_API_URL", &url)]).await;
+        st.repos.cache.write("spacex", serde_json::json!({ "name": "Crew-12" })).await.unwrap();
+        let first = st.repos.cache.latest("spacex").await.unwrap().unwrap();
+
+        let run = st.refresh.start(&st, &[SpaceSource::Spacex]);
+        let status = run.wait(Duration::from_secs(10)).await;
+        assert_eq!((status.status, status.ok), ("done", true));
+        let r = &status.sources[0];
+        // данные не изменились: версия та же, сдвинулся только last_seen_at
+        assert_eq!(r.fetched_at, Some(first.fetched_at));
+        assert!(r.last_seen_at.is_some_and(|t| t > first.last_seen_at));
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let job = st.jobs.status(&*st.repos.jobs).await.unwrap().into_iter().find(|j| j.name == "spacex").unwrap();
+        assert_eq!((job.run_count, job.last_error), (1, None));
+        assert!(st.refresh.get(&status.id).is_some());
+    }
+
+    #[tokio::test]
+    async fn failed_source_reports_error_code() {
+        let (url, _) = testing::upstream(vec![Reply::status(404)]).await;
+        let st = AppState::in_memory(&[("SPACEX_API_URL", &url)]).await;
+        let status = st.refresh.start(&st, &[SpaceSource::Spacex]).wait(Duration::from_secs(10)).await;
+        let r = &status.sources[0];
+        assert!(!status.ok && !r.ok);
+        assert!(r.error_code.is_some() && r.error.is_some());
+        let job = st.jobs.status(&*st.repos.jobs).await.unwrap().into_iter().find(|j| j.name == "spacex").unwrap();
+        assert!(job.last_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_join_one_run() {
+        let (url, hits) = testing::upstream(vec![Reply::json(serde_json::json!({ "name": "x" }))]).await;
+        let st = AppState::in_memory(&[("SPACEX_API_URL", &url)]).await;
+        let a = st.refresh.start(&st, &[SpaceSource::Spacex]);
+        let b = st.refresh.start(&st, &[SpaceSource::Spacex]);
+        let (a, b) = (a.wait(Duration::from_secs(10)).await, b.wait(Duration::from_secs(10)).await);
+        assert!(a.ok && b.ok);
+        assert!(!a.sources[0].joined && b.sources[0].joined);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+}
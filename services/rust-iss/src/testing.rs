@@ -0,0 +1,75 @@
+//! Общие помощники тестов: заглушка апстрима и приложение на случайном порту
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::IntoResponse;
+use axum::Router;
+use serde_json::Value;
+
+/// Набор элементов МКС на 2008-09-20 (пример из описания формата TLE)
+pub const ISS_TLE: [&str; 2] = [
+    "1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927",
+    "2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537",
+];
+
+/// Один ответ заглушки
+#[derive(Clone)]
+pub struct Reply {
+    status: u16,
+    retry_after: Option<&'static str>,
+    body: Value,
+}
+
+impl Reply {
+    pub fn json(body: Value) -> Self {
+        Self { status: 200, retry_after: None, body }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self { status, retry_after: None, body: serde_json::json!({ "status": status }) }
+    }
+
+    pub fn retry_after(mut self, v: &'static str) -> Self {
+        self.retry_after = Some(v);
+        self
+    }
+}
+
+/// Апстрим-заглушка: на любой путь отдаёт ответы по очереди (последний повторяется) и считает запросы
+pub async fn upstream(replies: Vec<Reply>) -> (String, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let queue = Arc::new(Mutex::new(VecDeque::from(replies)));
+    let counter = hits.clone();
+    let app = Router::new().fallback(move || {
+        let (hits, queue) = (counter.clone(), queue.clone());
+        async move {
+            hits.fetch_add(1, Ordering::SeqCst);
+            let reply = {
+                let mut q = queue.lock().unwrap();
+                if q.len() > 1 { q.pop_front().unwrap() } else { q[0].clone() }
+            };
+            let mut resp = (StatusCode::from_u16(reply.status).unwrap(), axum::Json(reply.body)).into_response();
+            if let Some(v) = reply.retry_after {
+                resp.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from_static(v));
+            }
+            resp
+        }
+    });
+    (serve(app).await, hits)
+}
+
+/// Поднимает `app` на 127.0.0.1 со случайным портом; возвращает базовый URL
+pub async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+/// Статус и тело ответа (конверт `{ok, data | error}`)
+pub async fn call(method: reqwest::Method, url: &str) -> (u16, Value) {
+    let resp = reqwest::Client::new().request(method, url).send().await.unwrap();
+    (resp.status().as_u16(), resp.json().await.unwrap())
+}